@@ -0,0 +1,100 @@
+//! Territory analysis: which empty squares each player can still hope to cover.
+//!
+//! A player's reachable squares start from the tiles of their currently legal moves. From there,
+//! any empty square that doesn't touch one of their tiles along an edge can be reached if it
+//! touches a reachable square, orthogonally (the same piece) or diagonally (a piece attached at a
+//! corner). This ignores the shapes of the remaining pieces and the squares future pieces will
+//! rule out, so it's an optimistic bound: squares outside it can never be covered by that player.
+//! Squares neither player can reach are dead.
+
+use serde::{Deserialize, Serialize};
+
+use crate::bitboard::Bitboard;
+use crate::board::{BoardState, Player};
+use crate::movegen::{Move, ORIENTATIONS_BITBOARD_DATA, placements_for};
+
+/// Reachable and dead empty squares for both players.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Territory {
+    pub player_a: Bitboard,
+    pub player_b: Bitboard,
+    pub dead: Bitboard,
+}
+
+/// Square counts of a `Territory`, for use as evaluation features.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TerritoryCounts {
+    /// Squares player A can reach, including contested ones
+    pub player_a: u32,
+    pub player_b: u32,
+    /// Squares only player A can reach
+    pub exclusive_a: u32,
+    pub exclusive_b: u32,
+    /// Squares both players can reach
+    pub contested: u32,
+    pub dead: u32,
+}
+
+// Tiles covered by the player's currently legal moves
+fn move_squares(board: &BoardState, player: Player) -> Bitboard {
+    let mut squares = Bitboard::EMPTY;
+
+    for m in placements_for(board, player) {
+        let mov = Move::unpack(m);
+        squares.set_rows_at(
+            &ORIENTATIONS_BITBOARD_DATA[mov.movetype as usize][mov.orientation as usize],
+            mov.x,
+            mov.y,
+        );
+    }
+
+    squares
+}
+
+/// The empty squares `player` could still cover with some sequence of their remaining pieces.
+/// See the module docs for how this is approximated.
+pub fn reachable_squares(board: &BoardState, player: Player) -> Bitboard {
+    let my_bitboard = board.bit_board(player);
+    let available = !(board.occupied() | my_bitboard.edge_neighbours());
+
+    move_squares(board, player).flood_fill(&available)
+}
+
+/// Reachable squares for both players, and the empty squares neither can reach.
+pub fn territory(board: &BoardState) -> Territory {
+    let player_a = reachable_squares(board, Player::White);
+    let player_b = reachable_squares(board, Player::Black);
+
+    Territory {
+        player_a,
+        player_b,
+        dead: !(board.occupied() | player_a | player_b),
+    }
+}
+
+impl Territory {
+    /// Squares only `player` can reach.
+    pub fn exclusive(&self, player: Player) -> Bitboard {
+        match player {
+            Player::White => self.player_a & !self.player_b,
+            Player::Black => self.player_b & !self.player_a,
+        }
+    }
+
+    /// Squares both players can reach.
+    pub fn contested(&self) -> Bitboard {
+        self.player_a & self.player_b
+    }
+
+    pub fn counts(&self) -> TerritoryCounts {
+        TerritoryCounts {
+            player_a: self.player_a.count(),
+            player_b: self.player_b.count(),
+            exclusive_a: self.exclusive(Player::White).count(),
+            exclusive_b: self.exclusive(Player::Black).count(),
+            contested: self.contested().count(),
+            dead: self.dead.count(),
+        }
+    }
+}
@@ -1,8 +1,13 @@
 use blok_rs::board::{BoardState, GameResult, StartPosition};
-use blok_rs::movegen::generate_moves;
+use blok_rs::game::{Game, now_ms};
+use blok_rs::game_file::date_string;
+use blok_rs::movegen::{Move, generate_moves};
 use rand::rng;
 use rand::seq::IndexedRandom;
+use std::fs::OpenOptions;
+use std::io::Write;
 use std::process::{Command, Stdio};
+use std::time::Instant;
 
 /// Path to the two engine executables to compare.
 /// You may want to change these to the correct paths for your system.
@@ -10,6 +15,8 @@ const ENGINE1_PATH: &str = "./executables/hce-latest";
 const ENGINE2_PATH: &str = "./executables/nn-latest";
 const NUM_GAME_PAIRS: usize = 50;
 const OPENING_PLIES: usize = 6;
+/// Every game played is appended to this file, in the `game_file` format.
+const GAME_FILE_PATH: &str = "./compare_games.txt";
 
 fn generate_opening() -> Vec<u32> {
     let mut board = BoardState::new(StartPosition::Corner);
@@ -84,29 +91,36 @@ fn main() {
 /// Plays a single game between two engines, returning the result from the perspective of the first engine (as White).
 /// The game starts from the given opening moves.
 fn play_game(engine_white: &str, engine_black: &str, opening_moves: &[u32]) -> GameResult {
-    let mut board = BoardState::new(StartPosition::Corner);
-    let mut moves: Vec<u32> = Vec::new();
+    let mut game = Game::new(StartPosition::Corner);
+    game.metadata_mut()
+        .insert("playerA".to_string(), engine_white.to_string());
+    game.metadata_mut()
+        .insert("playerB".to_string(), engine_black.to_string());
+    game.metadata_mut()
+        .insert("openingPlies".to_string(), opening_moves.len().to_string());
+    game.metadata_mut()
+        .insert("date".to_string(), date_string(now_ms()));
     let mut move_strings: Vec<String> = Vec::new();
 
     // Play the opening moves
     for &m in opening_moves {
-        board.do_move(m);
-        moves.push(m);
+        game.play(m).expect("Illegal opening move");
         move_strings.push(m.to_string());
     }
 
     // Determine which engine is to move next
     // If opening_moves.len() is even, it's White's turn (engine_white)
     // If odd, it's Black's turn (engine_black)
-    let mut current_engine = if opening_moves.len() % 2 == 0 {
+    let mut current_engine = if opening_moves.len().is_multiple_of(2) {
         engine_white
     } else {
         engine_black
     };
 
-    while board.game_result() == GameResult::InProgress {
+    while game.board().game_result() == GameResult::InProgress {
         // Prepare the move list as a space-separated string
         let moves_arg = move_strings.join(" ");
+        let think_start = Instant::now();
 
         // Call the engine executable
         let output = Command::new(current_engine)
@@ -127,7 +141,7 @@ fn play_game(engine_white: &str, engine_black: &str, opening_moves: &[u32]) -> G
         let best_move_str = stdout.trim();
 
         // Find the move in the legal moves
-        let legal_moves = generate_moves(&board);
+        let legal_moves = generate_moves(game.board());
         let parsed_move = legal_moves
             .iter()
             .find(|&&m| m.to_string() == best_move_str);
@@ -141,15 +155,16 @@ fn play_game(engine_white: &str, engine_black: &str, opening_moves: &[u32]) -> G
                     best_move_str,
                     legal_moves
                         .iter()
-                        .map(|m| m.to_string())
+                        .map(|&m| format!("{} ({})", m, Move::to_notation(m)))
                         .collect::<Vec<_>>()
                 );
                 std::process::exit(1);
             }
         };
 
-        board.do_move(chosen_move);
-        moves.push(chosen_move);
+        let think_time_ms = think_start.elapsed().as_millis() as u64;
+        game.play_with_stats(chosen_move, Some(think_time_ms), None)
+            .expect("Move was found among the legal moves");
         move_strings.push(chosen_move.to_string());
 
         // Alternate engines
@@ -160,10 +175,47 @@ fn play_game(engine_white: &str, engine_black: &str, opening_moves: &[u32]) -> G
         };
     }
 
+    let result = game.annotate_result();
+
+    println!("{}", game.board());
     println!(
-        "Moves: {:?} [player a: {}, player b: {}]",
-        moves, engine_white, engine_black
+        "Moves: {} [player a: {}, player b: {}]",
+        game.moves()
+            .iter()
+            .map(|&m| Move::to_notation(m))
+            .collect::<Vec<_>>()
+            .join(" "),
+        engine_white,
+        engine_black
     );
+    save_game(&game);
 
-    board.game_result()
+    result
+}
+
+/// Append a finished game to `GAME_FILE_PATH`, separated from the previous one by a blank line.
+fn save_game(game: &Game) {
+    let text = match game.record().to_game_file() {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("Failed to write game to {}: {}", GAME_FILE_PATH, e);
+            return;
+        }
+    };
+
+    let result = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(GAME_FILE_PATH)
+        .and_then(|mut file| {
+            let empty = file.metadata()?.len() == 0;
+            if !empty {
+                writeln!(file)?;
+            }
+            file.write_all(text.as_bytes())
+        });
+
+    if let Err(e) = result {
+        eprintln!("Failed to write game to {}: {}", GAME_FILE_PATH, e);
+    }
 }
@@ -1,7 +1,9 @@
 use blok_rs::{
     board::{BoardState, GameResult, Player, StartPosition},
+    game::Game,
     mcts::MonteCarlo,
     movegen::{NULL_MOVE, generate_moves},
+    record::{GameRecord, SearchStats},
 };
 
 use rand::seq::IndexedRandom;
@@ -17,6 +19,7 @@ use std::{
 fn main() {
     let start = Instant::now();
     let mut file = BufWriter::new(File::create("data.bin").unwrap());
+    let mut games_file = BufWriter::new(File::create("games.jsonl").unwrap());
     let total_written = AtomicU64::new(0);
 
     // Process in batches to allow periodic writes
@@ -27,13 +30,13 @@ fn main() {
         let batch_end = (batch_start + batch_size).min(total_games);
 
         // Collect batch results
-        let results: Vec<Vec<[u32; 15]>> = (batch_start..batch_end)
+        let results: Vec<(Vec<[u32; 15]>, GameRecord)> = (batch_start..batch_end)
             .into_par_iter()
             .map(|_| playout())
             .collect();
 
         // Write batch results
-        for packed_positions in results {
+        for (packed_positions, record) in results {
             let bytes = packed_positions
                 .iter()
                 .flat_map(|p| serialize(*p))
@@ -41,10 +44,13 @@ fn main() {
 
             file.write_all(&bytes).unwrap();
             total_written.fetch_add(packed_positions.len() as u64, Ordering::Relaxed);
+
+            writeln!(games_file, "{}", serde_json::to_string(&record).unwrap()).unwrap();
         }
 
         // Flush to disk periodically
         file.flush().unwrap();
+        games_file.flush().unwrap();
         println!(
             "Written {} positions so far",
             total_written.load(Ordering::Relaxed)
@@ -59,8 +65,8 @@ fn main() {
     );
 }
 
-fn playout() -> Vec<[u32; 15]> {
-    let mut board = BoardState::new(StartPosition::Corner);
+fn playout() -> (Vec<[u32; 15]>, GameRecord) {
+    let mut game = Game::new(StartPosition::Corner);
     let mut mcts = MonteCarlo::new();
     let mut rng = rand::rng();
 
@@ -68,9 +74,9 @@ fn playout() -> Vec<[u32; 15]> {
 
     // opening: skip opening moves
     for _ in 0..6 {
-        let moves = generate_moves(&board);
+        let moves = generate_moves(game.board());
         let random_move = moves.choose(&mut rng).unwrap();
-        board.do_move(*random_move);
+        game.play_engine_move(*random_move, None, None);
     }
 
     // let bad_opening = vec![32768, 67152, 34953, 69168, 6162, 70730];
@@ -78,29 +84,34 @@ fn playout() -> Vec<[u32; 15]> {
     //     board.do_move(m);
     // }
 
-    while board.game_result() == GameResult::InProgress {
-        mcts.run_search(&board, "eval");
+    while game.board().game_result() == GameResult::InProgress {
+        let search_start = Instant::now();
+        mcts.run_search(game.board(), "eval");
         let (wins, plays) = mcts.get_stats();
 
         let chosen_move = mcts.best_play().unwrap();
 
         mcts.clear();
 
-        let packed = pack(&board, wins, plays);
+        let packed = pack(game.board(), wins, plays);
 
         // stop recording positions after the game is close to decided
 
         let probability = wins as f64 / plays as f64;
 
-        let should_stop = chosen_move == NULL_MOVE || probability < 0.1 || probability > 0.9;
+        let should_stop = chosen_move == NULL_MOVE || !(0.1..=0.9).contains(&probability);
         if !should_stop {
             packed_positions.push(packed);
         }
 
-        board.do_move(chosen_move);
+        game.play_engine_move(
+            chosen_move,
+            Some(search_start.elapsed().as_millis() as u64),
+            Some(SearchStats { plays, wins }),
+        );
     }
 
-    let result = board.game_result();
+    let result = game.annotate_result();
 
     // annotate all of the packed positions with the result (absolute result)
     for packed in packed_positions.iter_mut() {
@@ -114,19 +125,20 @@ fn playout() -> Vec<[u32; 15]> {
         packed[14] |= result_bits << 30;
     }
 
-    packed_positions
+    (packed_positions, game.to_record())
 }
 
 //Note: nevermind do not flip the board!!!!! (store stm in the last row)
 fn pack(board: &BoardState, n_wins: usize, n_plays: usize) -> [u32; 15] {
     let mut packed: [u32; 15] = [0; 15];
 
-    #[allow(clippy::needless_range_loop)]
-    for i in 0..14 {
-        let player_a_data = board.player_a_bit_board[i];
-        let player_b_data = board.player_b_bit_board[i];
-
-        packed[i] = player_a_data as u32 | (player_b_data as u32) << 16;
+    let rows = board
+        .player_a_bit_board
+        .rows()
+        .iter()
+        .zip(board.player_b_bit_board.rows());
+    for (packed_row, (&player_a_data, &player_b_data)) in packed.iter_mut().zip(rows) {
+        *packed_row = player_a_data as u32 | (player_b_data as u32) << 16;
     }
     // n_wins, n_plays each take 14 bits (so max of 2^14 =)
     // use the top two bits to store result (00 = win, 01 = loss, 10 = tie)
@@ -1,7 +1,7 @@
 use blok_rs::{
     board::{BoardState, StartPosition},
     mcts::MonteCarlo,
-    movegen::generate_moves,
+    movegen::{Move, check_move},
 };
 use std::env;
 
@@ -10,32 +10,49 @@ const THINK_DURATION_MS: usize = 1000;
 fn main() {
     // Read moves from command line argument
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: {} \"<move1> <move2> ...\"", args[0]);
-        std::process::exit(1);
-    }
-    let move_strs: Vec<&str> = args[1].split_whitespace().collect();
-
-    let mut board = BoardState::new(StartPosition::Corner);
+    let (mut board, move_strs): (BoardState, Vec<&str>) = match args.as_slice() {
+        [_, moves] => (
+            BoardState::new(StartPosition::Corner),
+            moves.split_whitespace().collect(),
+        ),
+        [_, flag, position] if flag == "--position" => match BoardState::from_notation(position) {
+            Ok(board) => (board, Vec::new()),
+            Err(e) => {
+                eprintln!("Invalid position: {}", e);
+                std::process::exit(1);
+            }
+        },
+        _ => {
+            eprintln!("Usage: {} \"<move1> <move2> ...\"", args[0]);
+            eprintln!("       {} --position \"<notation>\"", args[0]);
+            std::process::exit(1);
+        }
+    };
 
     // Play the moves from the command line
     for mstr in move_strs {
-        // Try to parse the move from string
-        let legal_moves = generate_moves(&board);
-        let parsed_move = legal_moves.iter().find(|&&m| m.to_string() == mstr);
+        // Try to parse the move from string, either packed or in move notation
+        let parsed_move = match mstr.parse::<u32>() {
+            Ok(m) => check_move(&board, m).map(|_| m).map_err(|e| e.to_string()),
+            Err(_) => Move::from_notation(mstr, &board).map_err(|e| e.to_string()),
+        };
         match parsed_move {
-            Some(&m) => board.do_move(m),
-            None => {
-                eprintln!("Illegal or unrecognized move: {}", mstr);
+            Ok(m) => board.do_move(m),
+            Err(e) => {
+                eprintln!("Illegal or unrecognized move {}: {}", mstr, e);
                 std::process::exit(1);
             }
         }
     }
 
+    eprintln!("{}", board);
+
     // Now think and print the best response move
     let mut mcts = MonteCarlo::new();
+    mcts.prune_root_symmetry = true;
 
     mcts.run_search_timeout(&board, THINK_DURATION_MS);
     let best_move = mcts.best_play().unwrap();
+    eprintln!("Best move: {}", Move::to_notation(best_move));
     println!("{}", best_move);
 }
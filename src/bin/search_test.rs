@@ -9,6 +9,7 @@ pub fn main() {
     mcts.run_search(&board, "easy");
     let best_move = mcts.best_play().unwrap();
     let stats = mcts.get_stats();
+    println!("{}", board);
     println!(
         "Result of search: {} (eval ~ {}/{})",
         best_move, stats.0, stats.1
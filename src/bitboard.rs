@@ -0,0 +1,251 @@
+//! A set of squares on the 14x14 board, stored as one `u16` per row (row `y`, bit `x`).
+//!
+//! Directions follow the board coordinates: "left" is towards `x = 0` and "up" towards `y = 0`.
+//! Operations never set bits outside the board.
+
+use std::ops::{
+    BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Index, IndexMut, Not,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::Coord;
+
+const ROW_MASK: u16 = 0x3fff;
+
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default,
+)]
+#[serde(transparent)]
+pub struct Bitboard(pub [u16; 14]);
+
+impl Bitboard {
+    pub const EMPTY: Bitboard = Bitboard([0; 14]);
+    pub const FULL: Bitboard = Bitboard([ROW_MASK; 14]);
+
+    pub fn from_square(c: Coord) -> Bitboard {
+        let mut bitboard = Bitboard::EMPTY;
+        bitboard.set(c);
+        bitboard
+    }
+
+    pub fn from_squares(squares: impl IntoIterator<Item = Coord>) -> Bitboard {
+        let mut bitboard = Bitboard::EMPTY;
+        for c in squares {
+            bitboard.set(c);
+        }
+        bitboard
+    }
+
+    pub fn rows(&self) -> &[u16; 14] {
+        &self.0
+    }
+
+    pub fn get(&self, c: Coord) -> bool {
+        c.in_bounds() && self.0[c.y as usize] & (1 << c.x) != 0
+    }
+
+    /// Add a square, which must be on the board.
+    pub fn set(&mut self, c: Coord) {
+        debug_assert!(c.in_bounds(), "({}, {}) is off the board", c.x, c.y);
+        self.0[c.y as usize] |= 1 << c.x;
+    }
+
+    /// Remove a square, which must be on the board.
+    pub fn clear(&mut self, c: Coord) {
+        debug_assert!(c.in_bounds(), "({}, {}) is off the board", c.x, c.y);
+        self.0[c.y as usize] &= !(1 << c.x);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&row| row == 0)
+    }
+
+    pub fn count(&self) -> u32 {
+        self.0.iter().map(|row| row.count_ones()).sum()
+    }
+
+    pub fn intersects(&self, other: &Bitboard) -> bool {
+        self.0.iter().zip(&other.0).any(|(a, b)| a & b != 0)
+    }
+
+    /// Whether any bits are set outside the 14 columns of the board. Can only happen for
+    /// bitboards built from raw rows.
+    pub fn has_bits_outside_board(&self) -> bool {
+        self.0.iter().any(|&row| row & !ROW_MASK != 0)
+    }
+
+    /// Set the squares of a shape given as rows of bits (e.g. a piece's entry in
+    /// `ORIENTATIONS_BITBOARD_DATA`), with its top left corner at `(x, y)`. The shape must fit on
+    /// the board.
+    pub fn set_rows_at(&mut self, rows: &[u16], x: u8, y: u8) {
+        for (dy, row) in rows.iter().enumerate() {
+            self.0[y as usize + dy] |= row << x;
+        }
+    }
+
+    /// Whether a shape given as rows of bits, placed as in `set_rows_at`, covers any square of
+    /// this bitboard.
+    pub fn intersects_rows_at(&self, rows: &[u16], x: u8, y: u8) -> bool {
+        rows.iter()
+            .enumerate()
+            .any(|(dy, row)| self.0[y as usize + dy] & (row << x) != 0)
+    }
+
+    /// Set the squares of a halo shape (e.g. a piece's entry in `ORIENTATIONS_BITBOARD_HALO_DATA`),
+    /// which starts one square above and to the left of the piece so it has room for the squares
+    /// around it. `(x, y)` is where the piece goes; halo squares off the board are dropped.
+    pub fn set_halo_rows_at(&mut self, rows: &[u16], x: u8, y: u8) {
+        for (dy, row) in rows.iter().enumerate() {
+            let board_y = y as usize + dy;
+            if board_y == 0 || board_y > 14 {
+                continue;
+            }
+            self.0[board_y - 1] |= ((row << x) >> 1) & ROW_MASK;
+        }
+    }
+
+    /// Whether a halo shape, placed as in `set_halo_rows_at`, covers any square of this bitboard.
+    pub fn intersects_halo_rows_at(&self, rows: &[u16], x: u8, y: u8) -> bool {
+        rows.iter().enumerate().any(|(dy, row)| {
+            let board_y = y as usize + dy;
+            // shift the board row by 1 to match the halo data
+            board_y != 0 && board_y <= 14 && (row << x) & (self.0[board_y - 1] << 1) != 0
+        })
+    }
+
+    pub fn shift_left(&self) -> Bitboard {
+        Bitboard(self.0.map(|row| row >> 1))
+    }
+
+    pub fn shift_right(&self) -> Bitboard {
+        Bitboard(self.0.map(|row| (row << 1) & ROW_MASK))
+    }
+
+    pub fn shift_up(&self) -> Bitboard {
+        Bitboard(std::array::from_fn(
+            |y| if y < 13 { self.0[y + 1] } else { 0 },
+        ))
+    }
+
+    pub fn shift_down(&self) -> Bitboard {
+        Bitboard(std::array::from_fn(
+            |y| if y > 0 { self.0[y - 1] } else { 0 },
+        ))
+    }
+
+    /// Squares that share an edge with a set square, not counting the set squares themselves.
+    pub fn edge_neighbours(&self) -> Bitboard {
+        (self.shift_left() | self.shift_right() | self.shift_up() | self.shift_down()) & !*self
+    }
+
+    /// Squares that share a corner with a set square, not counting the set squares themselves.
+    pub fn diagonal_neighbours(&self) -> Bitboard {
+        let horizontal = self.shift_left() | self.shift_right();
+        (horizontal.shift_up() | horizontal.shift_down()) & !*self
+    }
+
+    /// The set squares along with every square sharing an edge or a corner with one.
+    pub fn expand(&self) -> Bitboard {
+        let horizontal = *self | self.shift_left() | self.shift_right();
+        horizontal | horizontal.shift_up() | horizontal.shift_down()
+    }
+
+    /// Empty squares that touch a set square diagonally but not along an edge, and aren't in
+    /// `occupied`: where a player owning these tiles can attach a new piece.
+    pub fn corners(&self, occupied: &Bitboard) -> Bitboard {
+        self.diagonal_neighbours() & !self.edge_neighbours() & !*occupied
+    }
+
+    /// Grow the set squares through `within`, eight-way, until nothing more can be added. The
+    /// starting squares are kept even if they aren't in `within`.
+    pub fn flood_fill(&self, within: &Bitboard) -> Bitboard {
+        let mut filled = *self;
+        loop {
+            let next = filled | (filled.expand() & *within);
+            if next == filled {
+                return filled;
+            }
+            filled = next;
+        }
+    }
+
+    /// The set squares, row by row.
+    pub fn squares(&self) -> impl Iterator<Item = Coord> + '_ {
+        self.0.iter().enumerate().flat_map(|(y, &row)| {
+            (0..14u8)
+                .filter(move |x| row & (1 << x) != 0)
+                .map(move |x| Coord { x, y: y as u8 })
+        })
+    }
+}
+
+impl From<[u16; 14]> for Bitboard {
+    fn from(rows: [u16; 14]) -> Self {
+        Bitboard(rows)
+    }
+}
+
+impl Index<usize> for Bitboard {
+    type Output = u16;
+
+    fn index(&self, y: usize) -> &u16 {
+        &self.0[y]
+    }
+}
+
+impl IndexMut<usize> for Bitboard {
+    fn index_mut(&mut self, y: usize) -> &mut u16 {
+        &mut self.0[y]
+    }
+}
+
+impl BitAnd for Bitboard {
+    type Output = Bitboard;
+
+    fn bitand(self, rhs: Bitboard) -> Bitboard {
+        Bitboard(std::array::from_fn(|y| self.0[y] & rhs.0[y]))
+    }
+}
+
+impl BitOr for Bitboard {
+    type Output = Bitboard;
+
+    fn bitor(self, rhs: Bitboard) -> Bitboard {
+        Bitboard(std::array::from_fn(|y| self.0[y] | rhs.0[y]))
+    }
+}
+
+impl BitXor for Bitboard {
+    type Output = Bitboard;
+
+    fn bitxor(self, rhs: Bitboard) -> Bitboard {
+        Bitboard(std::array::from_fn(|y| self.0[y] ^ rhs.0[y]))
+    }
+}
+
+impl Not for Bitboard {
+    type Output = Bitboard;
+
+    fn not(self) -> Bitboard {
+        Bitboard(self.0.map(|row| !row & ROW_MASK))
+    }
+}
+
+impl BitAndAssign for Bitboard {
+    fn bitand_assign(&mut self, rhs: Bitboard) {
+        *self = *self & rhs;
+    }
+}
+
+impl BitOrAssign for Bitboard {
+    fn bitor_assign(&mut self, rhs: Bitboard) {
+        *self = *self | rhs;
+    }
+}
+
+impl BitXorAssign for Bitboard {
+    fn bitxor_assign(&mut self, rhs: Bitboard) {
+        *self = *self ^ rhs;
+    }
+}
@@ -1,9 +1,15 @@
 use std::collections::HashMap;
+use std::fmt;
 
-use crate::movegen::{NULL_MOVE, PIECE_DATA, update_move_cache, update_move_cache_from_null_move};
+use crate::bitboard::Bitboard;
+use crate::movegen::{
+    IllegalMove, Move, NULL_MOVE, ORIENTATION_DATA, PIECE_DATA, SHORT_BOUNDING_BOX_DATA,
+    check_move, count_cached_moves, placements_for, rebuild_move_cache, update_move_cache,
+};
 
 #[repr(u8)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
 pub enum Player {
     White = 0,
     Black = 1,
@@ -18,13 +24,17 @@ impl Player {
     }
 }
 
-#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(
+    serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord,
+)]
 pub struct Coord {
     pub x: u8,
     pub y: u8,
 }
 
-#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(
+    serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord,
+)]
 pub struct CoordOffset {
     pub x: i8,
     pub y: i8,
@@ -36,13 +46,34 @@ impl Coord {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
 pub enum StartPosition {
     Middle,
     Corner,
     MiddleBlokee,
 }
 
+impl StartPosition {
+    /// The name used for this start position by the websocket protocol and the notations.
+    pub fn name(&self) -> &'static str {
+        match self {
+            StartPosition::Middle => "middle",
+            StartPosition::Corner => "corner",
+            StartPosition::MiddleBlokee => "middleBlokee",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<StartPosition> {
+        match name {
+            "middle" => Some(StartPosition::Middle),
+            "corner" => Some(StartPosition::Corner),
+            "middleBlokee" => Some(StartPosition::MiddleBlokee),
+            _ => None,
+        }
+    }
+}
+
 pub fn get_start_position_coord(start_position: StartPosition) -> (Coord, Coord) {
     match start_position {
         StartPosition::Middle => (Coord { x: 4, y: 4 }, Coord { x: 9, y: 9 }),
@@ -51,13 +82,15 @@ pub fn get_start_position_coord(start_position: StartPosition) -> (Coord, Coord)
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
 pub struct Score {
     pub player_a: u32,
     pub player_b: u32,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
 pub enum GameResult {
     InProgress,
     PlayerAWon,
@@ -65,7 +98,83 @@ pub enum GameResult {
     Draw,
 }
 
-#[derive(Debug, Clone)]
+/// Why a position passed to `BoardState::from_placements` or `BoardState::from_bitboards` was
+/// rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionError {
+    InvalidPlayer(u8),
+    InvalidPiece(u8),
+    InvalidOrientation { piece: u8, orientation: u8 },
+    OutOfBounds { player: Player, piece: u8 },
+    DuplicatePiece { player: Player, piece: u8 },
+    Overlap(Coord),
+    TouchesOwnTile { player: Player, piece: u8 },
+    InvalidRemainingMask(Player),
+    TilesOutsideBoard(Player),
+    TileCountMismatch(Player),
+    MissingStartSquare(Player),
+    InvalidNullMoveCounter(u8),
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PositionError::InvalidPlayer(player) => write!(f, "invalid player index {}", player),
+            PositionError::InvalidPiece(piece) => write!(f, "invalid piece index {}", piece),
+            PositionError::InvalidOrientation { piece, orientation } => {
+                write!(f, "invalid orientation {} for piece {}", orientation, piece)
+            }
+            PositionError::OutOfBounds { player, piece } => {
+                write!(f, "{:?}'s piece {} lies outside the board", player, piece)
+            }
+            PositionError::DuplicatePiece { player, piece } => {
+                write!(f, "{:?} placed piece {} more than once", player, piece)
+            }
+            PositionError::Overlap(coord) => {
+                write!(f, "more than one tile on ({}, {})", coord.x, coord.y)
+            }
+            PositionError::TouchesOwnTile { player, piece } => write!(
+                f,
+                "{:?}'s piece {} shares an edge with another of their pieces",
+                player, piece
+            ),
+            PositionError::InvalidRemainingMask(player) => {
+                write!(
+                    f,
+                    "{:?}'s remaining piece mask has bits above piece 20",
+                    player
+                )
+            }
+            PositionError::TilesOutsideBoard(player) => {
+                write!(
+                    f,
+                    "{:?}'s bitboard has tiles outside the 14x14 board",
+                    player
+                )
+            }
+            PositionError::TileCountMismatch(player) => write!(
+                f,
+                "{:?}'s tile count doesn't match the pieces they have placed",
+                player
+            ),
+            PositionError::MissingStartSquare(player) => {
+                write!(
+                    f,
+                    "{:?} has placed pieces but doesn't cover their start square",
+                    player
+                )
+            }
+            PositionError::InvalidNullMoveCounter(counter) => {
+                write!(f, "null move counter {} is above 2", counter)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(try_from = "BoardStateData")]
 pub struct BoardState {
     // Player to move
     pub player: Player,
@@ -75,8 +184,8 @@ pub struct BoardState {
     pub player_b_remaining: u32,
 
     // Bitboards for tiles placed
-    pub player_a_bit_board: [u16; 14],
-    pub player_b_bit_board: [u16; 14],
+    pub player_a_bit_board: Bitboard,
+    pub player_b_bit_board: Bitboard,
 
     pub start_position: StartPosition,
 
@@ -86,6 +195,69 @@ pub struct BoardState {
     // Cached corner moves
     pub player_a_corner_moves: HashMap<Coord, Vec<u32>>,
     pub player_b_corner_moves: HashMap<Coord, Vec<u32>>,
+
+    // The cached corners that still have moves, kept in step with the caches
+    pub player_a_live_corners: Bitboard,
+    pub player_b_live_corners: Bitboard,
+}
+
+/// The serialized form of a `BoardState`. The corner move caches are derived data and are left
+/// out; they are rebuilt when the board is deserialized.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BoardStateData {
+    player: Player,
+    player_a_remaining: u32,
+    player_b_remaining: u32,
+    player_a_bit_board: Bitboard,
+    player_b_bit_board: Bitboard,
+    start_position: StartPosition,
+    null_move_counter: u8,
+}
+
+// Copies only the small fields, so serializing doesn't clone the caches
+impl From<&BoardState> for BoardStateData {
+    fn from(board: &BoardState) -> Self {
+        Self {
+            player: board.player,
+            player_a_remaining: board.player_a_remaining,
+            player_b_remaining: board.player_b_remaining,
+            player_a_bit_board: board.player_a_bit_board,
+            player_b_bit_board: board.player_b_bit_board,
+            start_position: board.start_position,
+            null_move_counter: board.null_move_counter,
+        }
+    }
+}
+
+impl serde::Serialize for BoardState {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        BoardStateData::from(self).serialize(serializer)
+    }
+}
+
+impl TryFrom<BoardStateData> for BoardState {
+    type Error = PositionError;
+
+    fn try_from(data: BoardStateData) -> Result<Self, Self::Error> {
+        if data.null_move_counter > 2 {
+            return Err(PositionError::InvalidNullMoveCounter(
+                data.null_move_counter,
+            ));
+        }
+
+        let mut board = BoardState::from_bitboards(
+            data.start_position,
+            data.player_a_bit_board,
+            data.player_b_bit_board,
+            data.player_a_remaining,
+            data.player_b_remaining,
+            data.player,
+        )?;
+        board.null_move_counter = data.null_move_counter;
+
+        Ok(board)
+    }
 }
 
 impl BoardState {
@@ -94,42 +266,247 @@ impl BoardState {
             player: Player::White,
             player_a_remaining: 0x1fffff,
             player_b_remaining: 0x1fffff,
-            player_a_bit_board: [0; 14],
-            player_b_bit_board: [0; 14],
+            player_a_bit_board: Bitboard::EMPTY,
+            player_b_bit_board: Bitboard::EMPTY,
             null_move_counter: 0,
             start_position,
             player_a_corner_moves: HashMap::new(),
             player_b_corner_moves: HashMap::new(),
+            player_a_live_corners: Bitboard::EMPTY,
+            player_b_live_corners: Bitboard::EMPTY,
+        }
+    }
+
+    /// Set up a position from the pieces both players have placed, without any move history.
+    /// Each `Move` carries its own player; the order of the placements doesn't matter.
+    pub fn from_placements(
+        start_position: StartPosition,
+        placements: &[Move],
+        player: Player,
+    ) -> Result<Self, PositionError> {
+        let mut bit_boards = [Bitboard::EMPTY; 2];
+        let mut remaining = [0x1fffffu32; 2];
+
+        for placement in placements {
+            let owner = match placement.player {
+                0 => Player::White,
+                1 => Player::Black,
+                other => return Err(PositionError::InvalidPlayer(other)),
+            };
+            let piece = placement.movetype;
+            let orientation = placement.orientation;
+
+            if piece as usize >= PIECE_DATA.len() {
+                return Err(PositionError::InvalidPiece(piece));
+            }
+            if orientation as usize >= ORIENTATION_DATA[piece as usize].len() {
+                return Err(PositionError::InvalidOrientation { piece, orientation });
+            }
+
+            let (bx, by) = SHORT_BOUNDING_BOX_DATA[piece as usize][orientation as usize];
+            // x and y are checked alone first, so adding the bounding box can't overflow
+            if placement.x > 13
+                || placement.y > 13
+                || placement.x + bx > 13
+                || placement.y + by > 13
+            {
+                return Err(PositionError::OutOfBounds {
+                    player: owner,
+                    piece,
+                });
+            }
+
+            let idx = owner as usize;
+            if remaining[idx] & (1 << piece) == 0 {
+                return Err(PositionError::DuplicatePiece {
+                    player: owner,
+                    piece,
+                });
+            }
+
+            let tiles = Bitboard::from_squares(
+                ORIENTATION_DATA[piece as usize][orientation as usize]
+                    .iter()
+                    .map(|tile| Coord {
+                        x: placement.x + tile.x,
+                        y: placement.y + tile.y,
+                    }),
+            );
+
+            let overlap = tiles & (bit_boards[0] | bit_boards[1]);
+            if let Some(c) = overlap.squares().next() {
+                return Err(PositionError::Overlap(c));
+            }
+            if tiles.intersects(&bit_boards[idx].edge_neighbours()) {
+                return Err(PositionError::TouchesOwnTile {
+                    player: owner,
+                    piece,
+                });
+            }
+
+            bit_boards[idx] |= tiles;
+            remaining[idx] &= !(1 << piece);
         }
+
+        Self::from_bitboards(
+            start_position,
+            bit_boards[0],
+            bit_boards[1],
+            remaining[0],
+            remaining[1],
+            player,
+        )
+    }
+
+    /// Set up a position from raw bitboards and remaining piece masks. The corner move caches
+    /// are rebuilt from scratch, so the result behaves as if the position had been played out.
+    pub fn from_bitboards(
+        start_position: StartPosition,
+        player_a_bit_board: Bitboard,
+        player_b_bit_board: Bitboard,
+        player_a_remaining: u32,
+        player_b_remaining: u32,
+        player: Player,
+    ) -> Result<Self, PositionError> {
+        let (start_a, start_b) = get_start_position_coord(start_position);
+
+        for (owner, bit_board, remaining, start) in [
+            (
+                Player::White,
+                &player_a_bit_board,
+                player_a_remaining,
+                start_a,
+            ),
+            (
+                Player::Black,
+                &player_b_bit_board,
+                player_b_remaining,
+                start_b,
+            ),
+        ] {
+            if remaining & !0x1fffff != 0 {
+                return Err(PositionError::InvalidRemainingMask(owner));
+            }
+
+            if bit_board.has_bits_outside_board() {
+                return Err(PositionError::TilesOutsideBoard(owner));
+            }
+
+            let tile_count = bit_board.count();
+            let placed_tiles: usize = (0..21)
+                .filter(|piece| remaining & (1 << piece) == 0)
+                .map(|piece| PIECE_DATA[piece].len())
+                .sum();
+            if tile_count as usize != placed_tiles {
+                return Err(PositionError::TileCountMismatch(owner));
+            }
+
+            if remaining != 0x1fffff && !bit_board.get(start) {
+                return Err(PositionError::MissingStartSquare(owner));
+            }
+        }
+
+        let overlap = player_a_bit_board & player_b_bit_board;
+        if let Some(c) = overlap.squares().next() {
+            return Err(PositionError::Overlap(c));
+        }
+
+        let mut board = Self {
+            player,
+            player_a_remaining,
+            player_b_remaining,
+            player_a_bit_board,
+            player_b_bit_board,
+            null_move_counter: 0,
+            start_position,
+            player_a_corner_moves: HashMap::new(),
+            player_b_corner_moves: HashMap::new(),
+            player_a_live_corners: Bitboard::EMPTY,
+            player_b_live_corners: Bitboard::EMPTY,
+        };
+        rebuild_move_cache(&mut board);
+
+        Ok(board)
     }
 
     pub fn is_game_over(&self) -> bool {
         self.null_move_counter >= 2
     }
 
+    /// Each player's score is the number of tiles they've placed.
     pub fn score(&self) -> Score {
-        let player_a_remaining = self.player_a_remaining;
-        let mut player_a_score = 0;
-        for i in 0..21 {
-            if player_a_remaining & (1 << i) == 0 {
-                player_a_score += PIECE_DATA[i as usize].len();
-            }
+        Score {
+            player_a: self.player_a_bit_board.count(),
+            player_b: self.player_b_bit_board.count(),
         }
+    }
 
-        let player_b_remaining = self.player_b_remaining;
-        let mut player_b_score = 0;
-        for i in 0..21 {
-            if player_b_remaining & (1 << i) == 0 {
-                player_b_score += PIECE_DATA[i as usize].len();
-            }
+    /// Squares covered by either player.
+    pub fn occupied(&self) -> Bitboard {
+        self.player_a_bit_board | self.player_b_bit_board
+    }
+
+    pub fn bit_board(&self, player: Player) -> &Bitboard {
+        match player {
+            Player::White => &self.player_a_bit_board,
+            Player::Black => &self.player_b_bit_board,
         }
+    }
 
-        Score {
-            player_a: player_a_score as u32,
-            player_b: player_b_score as u32,
+    /// The corners `player` has cached moves from.
+    pub fn live_corners(&self, player: Player) -> &Bitboard {
+        match player {
+            Player::White => &self.player_a_live_corners,
+            Player::Black => &self.player_b_live_corners,
         }
     }
 
+    /// How many pieces `player` could place if it were their turn (not counting the null move).
+    pub fn legal_move_count(&self, player: Player) -> usize {
+        if self.is_game_over() {
+            return 0;
+        }
+
+        let (remaining, corner_moves, live_corners) = match player {
+            Player::White => (
+                self.player_a_remaining,
+                &self.player_a_corner_moves,
+                &self.player_a_live_corners,
+            ),
+            Player::Black => (
+                self.player_b_remaining,
+                &self.player_b_corner_moves,
+                &self.player_b_live_corners,
+            ),
+        };
+
+        // the caches are only filled from the first move on
+        if remaining == 0x1fffff {
+            return placements_for(self, player).len();
+        }
+
+        count_cached_moves(corner_moves, live_corners)
+    }
+
+    /// How many of `player`'s corners have at least one legal move. Before their first move,
+    /// the start square counts as their only corner.
+    pub fn live_corner_count(&self, player: Player) -> usize {
+        if self.is_game_over() {
+            return 0;
+        }
+
+        let remaining = match player {
+            Player::White => self.player_a_remaining,
+            Player::Black => self.player_b_remaining,
+        };
+
+        if remaining == 0x1fffff {
+            return usize::from(!placements_for(self, player).is_empty());
+        }
+
+        self.live_corners(player).count() as usize
+    }
+
     pub fn game_result(&self) -> GameResult {
         if !self.is_game_over() {
             return GameResult::InProgress;
@@ -146,11 +523,10 @@ impl BoardState {
     // change states, incrementally update move cache
     pub fn do_move(&mut self, board_move: u32) {
         if board_move == NULL_MOVE {
+            // nothing changes on the board, so the move caches stay valid
             self.null_move_counter += 1;
             self.skip_turn();
 
-            update_move_cache_from_null_move(self);
-
             return;
         }
 
@@ -159,6 +535,15 @@ impl BoardState {
         update_move_cache(self, board_move);
     }
 
+    /// Play a move from an untrusted source, checking it with `check_move` first. The board is
+    /// left unchanged if the move is rejected.
+    pub fn try_do_move(&mut self, board_move: u32) -> Result<(), IllegalMove> {
+        check_move(self, board_move)?;
+        self.do_move(board_move);
+
+        Ok(())
+    }
+
     pub fn skip_turn(&mut self) {
         self.player = self.player.other();
     }
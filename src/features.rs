@@ -0,0 +1,204 @@
+//! Features of a move, computed without playing it, for move ordering, priors and playout
+//! policies.
+
+use serde::{Deserialize, Serialize};
+
+use crate::bitboard::Bitboard;
+use crate::board::{BoardState, Coord, Player};
+use crate::movegen::{
+    CORNER_ATTACHERS_DATA, Move, NULL_MOVE, ORIENTATIONS_BITBOARD_DATA,
+    ORIENTATIONS_BITBOARD_HALO_DATA, placements_for,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveFeatures {
+    /// Number of tiles in the piece
+    pub piece_size: u32,
+    /// Corner squares the mover gains, not counting ones they already had
+    pub new_corners: u32,
+    /// Opponent corners with legal moves where every move would be blocked by the piece
+    pub blocked_opponent_corners: u32,
+    /// Opponent moves that overlap the piece
+    pub blocked_opponent_moves: u32,
+    /// Manhattan distance from the centre of the piece's tiles to the centre of the board
+    pub centre_distance: f32,
+    /// Distance (in king moves) from the piece to the nearest opponent tile, `None` if the
+    /// opponent hasn't placed anything
+    pub opponent_distance: Option<u32>,
+    /// Opponent tiles that share an edge with the piece
+    pub opponent_contact: u32,
+    /// Whether any tile of the piece is on the edge of the board
+    pub edge_hugging: bool,
+}
+
+fn piece_bitboard(mov: &Move) -> Bitboard {
+    let mut piece = Bitboard::EMPTY;
+    piece.set_rows_at(
+        &ORIENTATIONS_BITBOARD_DATA[mov.movetype as usize][mov.orientation as usize],
+        mov.x,
+        mov.y,
+    );
+    piece
+}
+
+// The piece and the squares sharing an edge with it
+fn halo_bitboard(mov: &Move) -> Bitboard {
+    let mut halo = Bitboard::EMPTY;
+    halo.set_halo_rows_at(
+        &ORIENTATIONS_BITBOARD_HALO_DATA[mov.movetype as usize][mov.orientation as usize],
+        mov.x,
+        mov.y,
+    );
+    halo
+}
+
+// Squares touching a corner of the piece and none of its edges
+fn attacher_bitboard(mov: &Move) -> Bitboard {
+    let attachers = &CORNER_ATTACHERS_DATA[mov.movetype as usize][mov.orientation as usize];
+    let squares = attachers.iter().filter_map(|offset| {
+        let x = mov.x as i8 + offset.x;
+        let y = mov.y as i8 + offset.y;
+        ((0..14).contains(&x) && (0..14).contains(&y)).then_some(Coord {
+            x: x as u8,
+            y: y as u8,
+        })
+    });
+
+    Bitboard::from_squares(squares)
+}
+
+fn intersects_move(piece: &Bitboard, m: u32) -> bool {
+    let mov = Move::unpack(m);
+    piece.intersects_rows_at(
+        &ORIENTATIONS_BITBOARD_DATA[mov.movetype as usize][mov.orientation as usize],
+        mov.x,
+        mov.y,
+    )
+}
+
+/// The moves and corners of the player who isn't moving, which `move_features` checks each move
+/// against. They only depend on the position, so build them once and share them between all of
+/// the mover's moves.
+pub struct OpponentMoves<'a> {
+    moves: Vec<u32>,
+    // `None` before the opponent's first move, when their only corner is their start square
+    corners: Option<Vec<&'a [u32]>>,
+    // Squares covered by at least one of the moves
+    reach: Bitboard,
+}
+
+impl<'a> OpponentMoves<'a> {
+    /// The moves `opponent` could play in `board`.
+    pub fn new(board: &'a BoardState, opponent: Player) -> Self {
+        let moves = placements_for(board, opponent);
+        let corners = (!board.bit_board(opponent).is_empty()).then(|| {
+            let corner_moves = match opponent {
+                Player::White => &board.player_a_corner_moves,
+                Player::Black => &board.player_b_corner_moves,
+            };
+            corner_moves.values().map(Vec::as_slice).collect()
+        });
+
+        let mut reach = Bitboard::EMPTY;
+        for &m in &moves {
+            let mov = Move::unpack(m);
+            reach.set_rows_at(
+                &ORIENTATIONS_BITBOARD_DATA[mov.movetype as usize][mov.orientation as usize],
+                mov.x,
+                mov.y,
+            );
+        }
+
+        Self {
+            moves,
+            corners,
+            reach,
+        }
+    }
+
+    fn corners(&self) -> impl Iterator<Item = &[u32]> {
+        let start_square = self.corners.is_none().then_some(self.moves.as_slice());
+
+        self.corners.iter().flatten().copied().chain(start_square)
+    }
+}
+
+/// Features of playing `m` in `board`. `m` is assumed to be legal for the player it belongs to
+/// (e.g. from `generate_moves` or `placements_for`), and `opponent_moves` to hold the other
+/// player's moves in the same position. `None` for the null move.
+pub fn move_features(
+    board: &BoardState,
+    opponent_moves: &OpponentMoves,
+    m: u32,
+) -> Option<MoveFeatures> {
+    if m == NULL_MOVE {
+        return None;
+    }
+
+    let mov = Move::unpack(m);
+    let player = if mov.player == 0 {
+        Player::White
+    } else {
+        Player::Black
+    };
+    let opponent = player.other();
+    let my_bitboard = board.bit_board(player);
+    let their_bitboard = board.bit_board(opponent);
+    let occupied = board.occupied();
+
+    let piece = piece_bitboard(&mov);
+    let halo = halo_bitboard(&mov);
+
+    let existing_corners = my_bitboard.corners(&occupied);
+    let new_corners =
+        attacher_bitboard(&mov) & !occupied & !my_bitboard.edge_neighbours() & !existing_corners;
+
+    // Pieces away from all of the opponent's moves can't block any of them
+    let mut blocked_opponent_corners = 0;
+    let mut blocked_opponent_moves = 0;
+    if piece.intersects(&opponent_moves.reach) {
+        blocked_opponent_corners = opponent_moves
+            .corners()
+            .filter(|moves| !moves.is_empty() && moves.iter().all(|&m| intersects_move(&piece, m)))
+            .count() as u32;
+        blocked_opponent_moves = opponent_moves
+            .moves
+            .iter()
+            .filter(|&&m| intersects_move(&piece, m))
+            .count() as u32;
+    }
+
+    let tiles: Vec<Coord> = piece.squares().collect();
+    let piece_size = tiles.len() as u32;
+    let centre_x = tiles.iter().map(|c| c.x as f32).sum::<f32>() / piece_size as f32;
+    let centre_y = tiles.iter().map(|c| c.y as f32).sum::<f32>() / piece_size as f32;
+    let centre_distance = (centre_x - 6.5).abs() + (centre_y - 6.5).abs();
+
+    let opponent_distance = if their_bitboard.is_empty() {
+        None
+    } else {
+        let mut reach = *their_bitboard;
+        let mut distance = 0;
+        while !reach.intersects(&piece) {
+            reach = reach.expand();
+            distance += 1;
+        }
+        Some(distance)
+    };
+
+    let edge_hugging = tiles
+        .iter()
+        .any(|c| c.x == 0 || c.y == 0 || c.x == 13 || c.y == 13);
+
+    Some(MoveFeatures {
+        piece_size,
+        new_corners: new_corners.count(),
+        blocked_opponent_corners,
+        blocked_opponent_moves,
+        centre_distance,
+        opponent_distance,
+        opponent_contact: (halo & *their_bitboard).count(),
+        edge_hugging,
+    })
+}
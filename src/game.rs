@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::board::{BoardState, GameResult, StartPosition};
+use crate::movegen::IllegalMove;
+use crate::record::{GameRecord, MoveInfo, ReplayError, SearchStats};
+
+/// A game being played: the current board along with the full history that led to it, so it
+/// can be logged, replayed and rewound. The history is kept as a `GameRecord`, which is also how
+/// a game is saved.
+#[derive(Debug, Clone)]
+pub struct Game {
+    record: GameRecord,
+    initial_board: BoardState,
+    board: BoardState,
+}
+
+/// Milliseconds since the Unix epoch, as recorded in move timestamps.
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+impl Game {
+    pub fn new(start_position: StartPosition) -> Self {
+        let board = BoardState::new(start_position);
+
+        Self {
+            record: GameRecord::new(start_position),
+            initial_board: board.clone(),
+            board,
+        }
+    }
+
+    /// Start a game from a set-up position rather than an empty board.
+    pub fn from_position(board: BoardState) -> Self {
+        let mut record = GameRecord::new(board.start_position);
+        record.initial_position = Some(board.to_notation());
+
+        Self {
+            record,
+            initial_board: board.clone(),
+            board,
+        }
+    }
+
+    /// Load a game from a record, checking every move in it.
+    pub fn from_record(record: GameRecord) -> Result<Self, ReplayError> {
+        let initial_board = record.initial_board()?;
+        let board = record.replay()?;
+
+        Ok(Self {
+            record,
+            initial_board,
+            board,
+        })
+    }
+
+    pub fn board(&self) -> &BoardState {
+        &self.board
+    }
+
+    pub fn record(&self) -> &GameRecord {
+        &self.record
+    }
+
+    pub fn moves(&self) -> &[u32] {
+        &self.record.moves
+    }
+
+    /// The number of moves (including null moves) played so far.
+    pub fn ply(&self) -> usize {
+        self.record.moves.len()
+    }
+
+    pub fn metadata_mut(&mut self) -> &mut BTreeMap<String, String> {
+        &mut self.record.metadata
+    }
+
+    /// Play a move, rejecting it if it isn't legal.
+    pub fn play(&mut self, m: u32) -> Result<(), IllegalMove> {
+        self.play_with_stats(m, None, None)
+    }
+
+    /// Play a move chosen by an engine, recording how long it thought and its search stats.
+    pub fn play_with_stats(
+        &mut self,
+        m: u32,
+        think_time_ms: Option<u64>,
+        search: Option<SearchStats>,
+    ) -> Result<(), IllegalMove> {
+        self.board.try_do_move(m)?;
+        self.record_move(m, think_time_ms, search);
+
+        Ok(())
+    }
+
+    /// Play a move the engine generated itself, so it's known to be legal, without checking it
+    /// again. Moves from anywhere else should go through `play` or `play_with_stats`.
+    pub fn play_engine_move(
+        &mut self,
+        m: u32,
+        think_time_ms: Option<u64>,
+        search: Option<SearchStats>,
+    ) {
+        self.board.do_move(m);
+        self.record_move(m, think_time_ms, search);
+    }
+
+    fn record_move(&mut self, m: u32, think_time_ms: Option<u64>, search: Option<SearchStats>) {
+        // keep the annotations parallel to the moves, even if earlier moves had none
+        self.record.move_info.resize(
+            self.record.moves.len(),
+            MoveInfo {
+                timestamp_ms: 0,
+                think_time_ms: None,
+                search: None,
+            },
+        );
+        self.record.moves.push(m);
+        self.record.move_info.push(MoveInfo {
+            timestamp_ms: now_ms(),
+            think_time_ms,
+            search,
+        });
+
+        // a new move invalidates any earlier result annotation
+        self.record.result = None;
+        self.record.score = None;
+    }
+
+    /// The position after the first `ply` moves, or `None` if fewer moves have been played.
+    pub fn position_at(&self, ply: usize) -> Option<BoardState> {
+        if ply > self.ply() {
+            return None;
+        }
+
+        let mut board = self.initial_board.clone();
+        for &m in &self.record.moves[..ply] {
+            board.do_move(m);
+        }
+
+        Some(board)
+    }
+
+    /// Rewind the game to the position after the first `ply` moves, discarding the moves
+    /// played since. Returns `false` (and leaves the game as is) if `ply` is past the end.
+    pub fn jump_to(&mut self, ply: usize) -> bool {
+        let Some(board) = self.position_at(ply) else {
+            return false;
+        };
+
+        self.board = board;
+        self.record.moves.truncate(ply);
+        self.record.move_info.truncate(ply);
+        self.record.result = None;
+        self.record.score = None;
+
+        true
+    }
+
+    /// Replay the recorded moves from the initial position, checking that each is legal and that
+    /// they lead to the current board.
+    pub fn validate(&self) -> Result<(), ReplayError> {
+        let replayed = self.record.replay()?;
+        if replayed.to_notation() != self.board.to_notation() {
+            return Err(ReplayError::BoardMismatch);
+        }
+
+        Ok(())
+    }
+
+    /// Store the result and score of the current position in the record, and return the result.
+    pub fn annotate_result(&mut self) -> GameResult {
+        self.record.set_outcome(&self.board);
+        self.board.game_result()
+    }
+
+    pub fn to_record(&self) -> GameRecord {
+        self.record.clone()
+    }
+}
@@ -0,0 +1,480 @@
+//! A PGN-like text format for saving and exchanging games.
+//!
+//! A game is a block of header tags followed by the moves:
+//!
+//! ```text
+//! [startPosition "corner"]
+//! [playerA "./executables/hce-latest"]
+//! [playerB "./executables/nn-latest"]
+//! [date "2026-10-18"]
+//! [result "1-0"]
+//! [score "71-68"]
+//!
+//! 1. L5-0@a1 {think=512 plays=1000 wins=640} I5-1@j14 2. ...
+//! 1-0
+//! ```
+//!
+//! `startPosition` is required. `initialPosition` (a `BoardState::to_notation` string) is given
+//! for games that don't start on an empty board. `result` is `1-0` if player A won, `0-1` if
+//! player B won, `1/2-1/2` for a draw and `*` for an unfinished game, and the movetext ends with
+//! the same token. Any other tag is a `GameRecord::metadata` entry, under the same name, so
+//! metadata names can't be one of the tags above and may only use ASCII letters, digits, `_`,
+//! `-` and `.`.
+//!
+//! Moves use the move notation from [`crate::notation`], numbered every two plies. A comment in
+//! braces after a move holds its `MoveInfo`: any of `ts` (timestamp), `think` (think time) in
+//! milliseconds, and `plays`/`wins` for the engine's search stats. Files may hold several games
+//! one after another.
+
+use std::fmt;
+
+use crate::board::{GameResult, Score, StartPosition};
+use crate::movegen::Move;
+use crate::notation::MoveNotationError;
+use crate::record::{GameRecord, MoveInfo, ReplayError, SearchStats};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameFileError {
+    UnterminatedTag,
+    UnterminatedComment,
+    InvalidTag(String),
+    MissingStartPosition,
+    InvalidStartPosition(String),
+    InvalidResult(String),
+    InvalidScore(String),
+    InvalidComment(String),
+    CommentBeforeMove,
+    InitialPosition(ReplayError),
+    Move {
+        ply: usize,
+        error: MoveNotationError,
+    },
+    UnterminatedGame,
+    /// The text holds no game at all
+    NoGame,
+    /// A metadata name that can't be written as a tag, or would read back as another tag
+    InvalidMetadataName(String),
+}
+
+impl fmt::Display for GameFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameFileError::UnterminatedTag => write!(f, "unterminated header tag"),
+            GameFileError::UnterminatedComment => write!(f, "unterminated comment"),
+            GameFileError::InvalidTag(tag) => write!(f, "invalid header tag '{}'", tag),
+            GameFileError::MissingStartPosition => write!(f, "missing startPosition tag"),
+            GameFileError::InvalidStartPosition(name) => {
+                write!(f, "invalid start position '{}'", name)
+            }
+            GameFileError::InvalidResult(result) => write!(f, "invalid result '{}'", result),
+            GameFileError::InvalidScore(score) => write!(f, "invalid score '{}'", score),
+            GameFileError::InvalidComment(comment) => {
+                write!(f, "invalid move comment '{}'", comment)
+            }
+            GameFileError::CommentBeforeMove => write!(f, "move comment before the first move"),
+            GameFileError::InitialPosition(e) => write!(f, "{}", e),
+            GameFileError::Move { ply, error } => write!(f, "ply {}: {}", ply, error),
+            GameFileError::UnterminatedGame => write!(f, "game has no result token"),
+            GameFileError::NoGame => write!(f, "no game found"),
+            GameFileError::InvalidMetadataName(name) => {
+                write!(f, "invalid metadata name '{}'", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GameFileError {}
+
+fn result_token(result: Option<GameResult>) -> &'static str {
+    match result {
+        Some(GameResult::PlayerAWon) => "1-0",
+        Some(GameResult::PlayerBWon) => "0-1",
+        Some(GameResult::Draw) => "1/2-1/2",
+        Some(GameResult::InProgress) | None => "*",
+    }
+}
+
+fn parse_result_token(token: &str) -> Option<Option<GameResult>> {
+    match token {
+        "1-0" => Some(Some(GameResult::PlayerAWon)),
+        "0-1" => Some(Some(GameResult::PlayerBWon)),
+        "1/2-1/2" => Some(Some(GameResult::Draw)),
+        "*" => Some(None),
+        _ => None,
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// The tags the format itself uses, which metadata can't be stored under
+const RESERVED_TAGS: [&str; 4] = ["startPosition", "initialPosition", "result", "score"];
+
+fn is_valid_metadata_name(name: &str) -> bool {
+    !name.is_empty()
+        && !RESERVED_TAGS.contains(&name)
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+fn write_tag(out: &mut String, name: &str, value: &str) {
+    out.push_str(&format!("[{} \"{}\"]\n", name, escape(value)));
+}
+
+fn move_comment(info: &MoveInfo) -> Option<String> {
+    let mut fields = Vec::new();
+    if info.timestamp_ms != 0 {
+        fields.push(format!("ts={}", info.timestamp_ms));
+    }
+    if let Some(think) = info.think_time_ms {
+        fields.push(format!("think={}", think));
+    }
+    if let Some(search) = info.search {
+        fields.push(format!("plays={} wins={}", search.plays, search.wins));
+    }
+
+    if fields.is_empty() {
+        None
+    } else {
+        Some(format!("{{{}}}", fields.join(" ")))
+    }
+}
+
+fn parse_move_comment(comment: &str) -> Result<MoveInfo, GameFileError> {
+    let invalid = || GameFileError::InvalidComment(comment.to_string());
+
+    let mut info = MoveInfo {
+        timestamp_ms: 0,
+        think_time_ms: None,
+        search: None,
+    };
+    let mut plays = None;
+    let mut wins = None;
+
+    for field in comment.split_whitespace() {
+        let (key, value) = field.split_once('=').ok_or_else(invalid)?;
+        let value: u64 = value.parse().map_err(|_| invalid())?;
+        match key {
+            "ts" => info.timestamp_ms = value,
+            "think" => info.think_time_ms = Some(value),
+            "plays" => plays = Some(value as usize),
+            "wins" => wins = Some(value as usize),
+            _ => return Err(invalid()),
+        }
+    }
+
+    info.search = match (plays, wins) {
+        (Some(plays), Some(wins)) => Some(SearchStats { plays, wins }),
+        (None, None) => None,
+        _ => return Err(invalid()),
+    };
+
+    Ok(info)
+}
+
+impl GameRecord {
+    /// Write the game in the game file format described in the [`crate::game_file`] docs. Fails
+    /// if a metadata name can't be written as a tag.
+    pub fn to_game_file(&self) -> Result<String, GameFileError> {
+        if let Some(name) = self
+            .metadata
+            .keys()
+            .find(|name| !is_valid_metadata_name(name))
+        {
+            return Err(GameFileError::InvalidMetadataName(name.clone()));
+        }
+
+        let mut out = String::new();
+
+        write_tag(&mut out, "startPosition", self.start_position.name());
+        if let Some(position) = &self.initial_position {
+            write_tag(&mut out, "initialPosition", position);
+        }
+        for (name, value) in &self.metadata {
+            write_tag(&mut out, name, value);
+        }
+        write_tag(&mut out, "result", result_token(self.result));
+        if let Some(score) = self.score {
+            write_tag(
+                &mut out,
+                "score",
+                &format!("{}-{}", score.player_a, score.player_b),
+            );
+        }
+        out.push('\n');
+
+        let mut tokens: Vec<String> = Vec::new();
+        for (ply, &m) in self.moves.iter().enumerate() {
+            if ply % 2 == 0 {
+                tokens.push(format!("{}.", ply / 2 + 1));
+            }
+            tokens.push(Move::to_notation(m));
+            if let Some(comment) = self.move_info.get(ply).and_then(move_comment) {
+                tokens.push(comment);
+            }
+        }
+        tokens.push(result_token(self.result).to_string());
+
+        // wrap the movetext at 80 columns
+        let mut line_len = 0;
+        for token in tokens {
+            if line_len > 0 && line_len + 1 + token.len() > 80 {
+                out.push('\n');
+                line_len = 0;
+            } else if line_len > 0 {
+                out.push(' ');
+                line_len += 1;
+            }
+            line_len += token.len();
+            out.push_str(&token);
+        }
+        out.push('\n');
+
+        Ok(out)
+    }
+}
+
+/// Write several games into one file, separated by blank lines.
+pub fn write_games(records: &[GameRecord]) -> Result<String, GameFileError> {
+    Ok(records
+        .iter()
+        .map(|record| record.to_game_file())
+        .collect::<Result<Vec<_>, _>>()?
+        .join("\n"))
+}
+
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Word(String),
+}
+
+fn parse_tag(tag: &str) -> Result<(String, String), GameFileError> {
+    let invalid = || GameFileError::InvalidTag(tag.to_string());
+
+    let (name, value) = tag
+        .trim()
+        .split_once(char::is_whitespace)
+        .ok_or_else(invalid)?;
+    let value = value.trim();
+    let value = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .ok_or_else(invalid)?;
+
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.push(chars.next().ok_or_else(invalid)?),
+            '"' => return Err(invalid()),
+            _ => unescaped.push(c),
+        }
+    }
+
+    Ok((name.to_string(), unescaped))
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, GameFileError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '[' => {
+                // a tag runs to the closing bracket outside of its quoted value
+                let mut in_quotes = false;
+                let mut escaped = false;
+                let mut end = None;
+                chars.next();
+                for (i, c) in chars.by_ref() {
+                    match c {
+                        _ if escaped => escaped = false,
+                        '\\' => escaped = true,
+                        '"' => in_quotes = !in_quotes,
+                        ']' if !in_quotes => {
+                            end = Some(i);
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+                let end = end.ok_or(GameFileError::UnterminatedTag)?;
+                let (name, value) = parse_tag(&text[start + 1..end])?;
+                tokens.push(Token::Tag(name, value));
+            }
+            '{' => {
+                chars.next();
+                let end = chars
+                    .by_ref()
+                    .find(|&(_, c)| c == '}')
+                    .map(|(i, _)| i)
+                    .ok_or(GameFileError::UnterminatedComment)?;
+                tokens.push(Token::Comment(text[start + 1..end].to_string()));
+            }
+            _ => {
+                let mut end = text.len();
+                while let Some(&(i, c)) = chars.peek() {
+                    if c.is_whitespace() || c == '{' || c == '[' {
+                        end = i;
+                        break;
+                    }
+                    chars.next();
+                }
+                tokens.push(Token::Word(text[start..end].to_string()));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn is_move_number(word: &str) -> bool {
+    word.strip_suffix('.')
+        .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+#[derive(Default)]
+struct PendingGame {
+    tags: Vec<(String, String)>,
+    moves: Vec<String>,
+    comments: Vec<Option<String>>,
+}
+
+impl PendingGame {
+    fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.moves.is_empty()
+    }
+
+    fn finish(self, result_token: &str) -> Result<GameRecord, GameFileError> {
+        let mut start_position = None;
+        let mut record = GameRecord::new(StartPosition::Corner);
+
+        for (name, value) in self.tags {
+            match name.as_str() {
+                "startPosition" => {
+                    start_position = Some(
+                        StartPosition::from_name(&value)
+                            .ok_or(GameFileError::InvalidStartPosition(value))?,
+                    );
+                }
+                "initialPosition" => record.initial_position = Some(value),
+                "result" => {
+                    record.result =
+                        parse_result_token(&value).ok_or(GameFileError::InvalidResult(value))?;
+                }
+                "score" => {
+                    let score = value.split_once('-').and_then(|(a, b)| {
+                        Some(Score {
+                            player_a: a.parse().ok()?,
+                            player_b: b.parse().ok()?,
+                        })
+                    });
+                    record.score = Some(score.ok_or(GameFileError::InvalidScore(value))?);
+                }
+                _ => {
+                    record.metadata.insert(name, value);
+                }
+            }
+        }
+
+        record.start_position = start_position.ok_or(GameFileError::MissingStartPosition)?;
+
+        let result = parse_result_token(result_token)
+            .ok_or_else(|| GameFileError::InvalidResult(result_token.to_string()))?;
+        if record.result.is_some() && record.result != result {
+            return Err(GameFileError::InvalidResult(result_token.to_string()));
+        }
+        record.result = result;
+
+        // resolve the moves against the position they're played in
+        let mut board = record
+            .initial_board()
+            .map_err(GameFileError::InitialPosition)?;
+        let mut move_info = Vec::new();
+        for (ply, (notation, comment)) in self.moves.iter().zip(&self.comments).enumerate() {
+            let m = Move::from_notation(notation, &board)
+                .map_err(|error| GameFileError::Move { ply, error })?;
+            board.do_move(m);
+            record.moves.push(m);
+
+            move_info.push(match comment {
+                Some(comment) => parse_move_comment(comment)?,
+                None => MoveInfo {
+                    timestamp_ms: 0,
+                    think_time_ms: None,
+                    search: None,
+                },
+            });
+        }
+
+        if self.comments.iter().any(|c| c.is_some()) {
+            record.move_info = move_info;
+        }
+
+        Ok(record)
+    }
+}
+
+/// Read all the games in a game file. Every move is checked for legality.
+pub fn read_games(text: &str) -> Result<Vec<GameRecord>, GameFileError> {
+    let mut records = Vec::new();
+    let mut game = PendingGame::default();
+
+    for token in tokenize(text)? {
+        match token {
+            Token::Tag(name, value) => game.tags.push((name, value)),
+            Token::Comment(comment) => {
+                let last = game
+                    .comments
+                    .last_mut()
+                    .ok_or(GameFileError::CommentBeforeMove)?;
+                *last = Some(comment);
+            }
+            Token::Word(word) if is_move_number(&word) => {}
+            Token::Word(word) if parse_result_token(&word).is_some() => {
+                records.push(std::mem::take(&mut game).finish(&word)?);
+            }
+            Token::Word(word) => {
+                game.moves.push(word);
+                game.comments.push(None);
+            }
+        }
+    }
+
+    if !game.is_empty() {
+        return Err(GameFileError::UnterminatedGame);
+    }
+
+    Ok(records)
+}
+
+/// Read a file holding a single game.
+pub fn read_game(text: &str) -> Result<GameRecord, GameFileError> {
+    read_games(text)?
+        .into_iter()
+        .next()
+        .ok_or(GameFileError::NoGame)
+}
+
+/// Format a Unix timestamp in milliseconds as a `YYYY-MM-DD` UTC date, for the `date` tag.
+pub fn date_string(timestamp_ms: u64) -> String {
+    // civil-from-days, from Howard Hinnant's date algorithms
+    let days = (timestamp_ms / 86_400_000) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
@@ -1,3 +1,13 @@
+pub mod analysis;
+pub mod bitboard;
 pub mod board;
+pub mod features;
+pub mod game;
+pub mod game_file;
 pub mod mcts;
 pub mod movegen;
+pub mod notation;
+pub mod record;
+pub mod render;
+pub mod server;
+pub mod symmetry;
@@ -1,5 +1,5 @@
 pub mod monte_carlo;
 pub mod monte_carlo_node;
 
-pub use monte_carlo::MonteCarlo;
+pub use monte_carlo::{MonteCarlo, MoveStats, SearchBudget, SearchProgress};
 pub use monte_carlo_node::MonteCarloNode;
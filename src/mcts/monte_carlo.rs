@@ -1,15 +1,98 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
 
 use rand::prelude::IndexedRandom;
 use rand::rng;
+use serde::{Deserialize, Serialize};
 
 use crate::board::{BoardState, GameResult, Player};
 use crate::mcts::MonteCarloNode;
 use crate::movegen::generate_moves;
+use crate::symmetry::unique_moves;
+
+/// Search results for one of the root's moves. `wins` counts playouts won by the player making
+/// the move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MoveStats {
+    pub play: u32,
+    pub visits: usize,
+    pub wins: usize,
+}
+
+impl MoveStats {
+    pub fn win_rate(&self) -> f64 {
+        if self.visits == 0 {
+            0.
+        } else {
+            self.wins as f64 / self.visits as f64
+        }
+    }
+}
+
+/// How long a search runs: a number of iterations, or a time limit in milliseconds. Written as
+/// `{"iterations": 20000}` or `{"millis": 1500}` in config files.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum SearchBudget {
+    Iterations(usize),
+    Millis(u64),
+}
+
+impl SearchBudget {
+    /// The built-in budget for a difficulty, used unless the server is configured otherwise.
+    pub fn for_difficulty(difficulty: &str) -> SearchBudget {
+        SearchBudget::Iterations(match difficulty {
+            "test" => 1_000,
+            "eval" => 1_000,
+            "easy" => 10_000,
+            "medium" => 20_000,
+            "hard" => 100_000,
+            _ => 60_000,
+        })
+    }
+
+    /// `parts` out of `whole` of the budget.
+    pub fn fraction(&self, parts: usize, whole: usize) -> SearchBudget {
+        match *self {
+            SearchBudget::Iterations(n) => SearchBudget::Iterations(n * parts / whole),
+            SearchBudget::Millis(ms) => SearchBudget::Millis(ms * parts as u64 / whole as u64),
+        }
+    }
+
+    pub fn is_spent(&self, iterations: usize, elapsed: Duration) -> bool {
+        match *self {
+            SearchBudget::Iterations(n) => iterations >= n,
+            SearchBudget::Millis(ms) => elapsed >= Duration::from_millis(ms),
+        }
+    }
+}
+
+/// How a search is going, passed to the progress callback of `run_budget_with_progress`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchProgress {
+    /// Iterations done by this search, not counting any the tree already had
+    pub iterations: usize,
+    pub elapsed: Duration,
+    /// The most visited move at the root so far
+    pub best: MoveStats,
+    /// `best` and the line the search expects to follow it
+    pub pv: Vec<u32>,
+}
 
 pub struct MonteCarlo {
     ucb1_explore_param: f64,
     pub nodes: Vec<MonteCarloNode>,
+    // Only search one move of each class of symmetric moves at the root, when the position is
+    // symmetric (e.g. at the start of a Corner or Middle game)
+    pub prune_root_symmetry: bool,
+    // Checked after every iteration: once it's set, the search ends early and `best_play` gives
+    // the best move found so far. At least one iteration always runs.
+    pub stop: Option<Arc<AtomicBool>>,
+    // The least time between two calls of the progress callback
+    pub progress_interval: Duration,
+    // The position at the root of the tree, to tell whether a search can carry on from it
+    root_state: Option<BoardState>,
 }
 
 impl Default for MonteCarlo {
@@ -24,105 +107,258 @@ impl MonteCarlo {
             //TODO: what actually was it
             ucb1_explore_param: 0.,
             nodes: Vec::new(),
+            prune_root_symmetry: false,
+            stop: None,
+            progress_interval: Duration::from_millis(500),
+            root_state: None,
         }
     }
 
     // Clear the search to prepare for a new search
     pub fn clear(&mut self) {
         self.nodes.clear();
+        self.root_state = None;
     }
 
+    /// Search `state` for the difficulty's built-in budget. If the tree's root is `state` (see
+    /// `reroot`), the search carries on from it; otherwise it starts over.
     pub fn run_search(&mut self, state: &BoardState, difficulty: &str) {
-        self.make_root_node(state);
-        let iterations = match difficulty {
-            "test" => 1_000,
-            "eval" => 1_000,
-            "easy" => 10_000,
-            "medium" => 20_000,
-            "hard" => 100_000,
-            _ => 60_000,
-        };
-
-        for _ in 0..iterations {
-            let tree_state: &mut BoardState = &mut state.clone();
+        self.run_budget(state, SearchBudget::for_difficulty(difficulty));
+    }
 
-            let node_idx = self.select(tree_state);
-            let node = &self.nodes[node_idx];
+    /// Search `state` until the budget is spent, carrying on from the tree like `run_search`.
+    pub fn run_budget(&mut self, state: &BoardState, budget: SearchBudget) -> usize {
+        self.run_budget_with_progress(state, budget, &mut |_| {})
+    }
 
-            let winner = tree_state.game_result();
+    /// Like `run_budget`, calling `on_progress` every `progress_interval` while the search runs.
+    /// Returns the number of iterations done.
+    pub fn run_budget_with_progress(
+        &mut self,
+        state: &BoardState,
+        budget: SearchBudget,
+        on_progress: &mut dyn FnMut(&SearchProgress),
+    ) -> usize {
+        self.make_root_node(state);
+        let start_time = Instant::now();
+        let mut last_progress = start_time;
+        let mut iterations = 0;
 
-            if !node.is_leaf() && winner == GameResult::InProgress {
-                let new_node_idx = self.expand(node_idx, tree_state);
-                // the player to move on the expanded state, before the simulation (used to update the correct n_wins during backpropagation)
-                let player = tree_state.player;
-                let winner = self.simulate(tree_state);
+        while !budget.is_spent(iterations, start_time.elapsed()) {
+            self.iterate(state);
+            iterations += 1;
+            if self.is_stopped() {
+                break;
+            }
 
-                self.backpropagate(new_node_idx, winner, player);
-            } else {
-                self.backpropagate(node_idx, winner, tree_state.player);
+            if last_progress.elapsed() >= self.progress_interval
+                && let Some(progress) = self.progress(iterations, start_time.elapsed())
+            {
+                on_progress(&progress);
+                last_progress = Instant::now();
             }
         }
+
+        iterations
+    }
+
+    fn progress(&self, iterations: usize, elapsed: Duration) -> Option<SearchProgress> {
+        let best = *self.root_moves().first()?;
+
+        Some(SearchProgress {
+            iterations,
+            elapsed,
+            best,
+            pv: self.principal_variation(best.play),
+        })
     }
 
     pub fn run_search_timeout(&mut self, state: &BoardState, timeout: usize) {
-        self.make_root_node(state);
-        let start_time = Instant::now();
-        let mut iterations = 0;
+        let iterations = self.run_budget(state, SearchBudget::Millis(timeout as u64));
 
-        while Instant::now() - start_time < Duration::from_millis(timeout as u64) {
-            let tree_state: &mut BoardState = &mut state.clone();
+        eprintln!("Iterations classic: {}", iterations);
+    }
 
-            let node_idx = self.select(tree_state);
-            let node = &self.nodes[node_idx];
+    /// One round of selection, expansion, simulation and backpropagation from the root.
+    fn iterate(&mut self, state: &BoardState) {
+        let tree_state: &mut BoardState = &mut state.clone();
 
-            let winner = tree_state.game_result();
+        let node_idx = self.select(tree_state);
+        let node = &self.nodes[node_idx];
 
-            if !node.is_leaf() && winner == GameResult::InProgress {
-                let new_node_idx = self.expand(node_idx, tree_state);
-                // the player to move on the expanded state, before the simulation (used to update the correct n_wins during backpropagation)
-                let player = tree_state.player;
-                let winner = self.simulate(tree_state);
+        let winner = tree_state.game_result();
 
-                self.backpropagate(new_node_idx, winner, player);
-            } else {
-                self.backpropagate(node_idx, winner, tree_state.player);
-            }
+        if !node.is_leaf() && winner == GameResult::InProgress {
+            let new_node_idx = self.expand(node_idx, tree_state);
+            // the player to move on the expanded state, before the simulation (used to update the correct n_wins during backpropagation)
+            let player = tree_state.player;
+            let winner = self.simulate(tree_state);
 
-            iterations += 1;
+            self.backpropagate(new_node_idx, winner, player);
+        } else {
+            self.backpropagate(node_idx, winner, tree_state.player);
         }
+    }
 
-        eprintln!("Iterations classic: {}", iterations);
+    fn is_stopped(&self) -> bool {
+        self.stop
+            .as_ref()
+            .is_some_and(|stop| stop.load(Ordering::Relaxed))
     }
+
+    // Keeps the tree if its root is `state`, and starts a new one otherwise
     fn make_root_node(&mut self, state: &BoardState) {
-        let unexpanded_moves = generate_moves(state);
-        let new_idx = self.nodes.len();
+        let same_root = self
+            .root_state
+            .as_ref()
+            .is_some_and(|root| same_position(root, state));
+        if same_root && !self.nodes.is_empty() {
+            return;
+        }
 
-        if new_idx != 0 {
-            panic!("Root node not at 0");
+        self.clear();
+
+        let mut unexpanded_moves = generate_moves(state);
+        if self.prune_root_symmetry {
+            unexpanded_moves = unique_moves(state, &unexpanded_moves);
         }
-        let node = MonteCarloNode::new(new_idx, None, unexpanded_moves);
+        let node = MonteCarloNode::new(0, None, unexpanded_moves);
         self.nodes.push(node);
+        self.root_state = Some(state.clone());
     }
 
-    pub fn best_play(&mut self) -> Result<u32, &str> {
-        let node = &self.nodes[0];
+    /// Make the child reached by `play` the new root, keeping the search below it and dropping
+    /// the rest of the tree. If `play` wasn't expanded, the tree is cleared and false is
+    /// returned.
+    pub fn reroot(&mut self, play: u32) -> bool {
+        let new_root = self
+            .nodes
+            .first()
+            .and_then(|root| root.children.get(&play).copied().flatten());
+        let Some(new_root) = new_root else {
+            self.clear();
+            return false;
+        };
 
-        if !node.is_fully_expanded() {
-            println!("WARNING: Root node is not fully expanded");
+        // Number the kept nodes breadth first, so parents still come before their children
+        let mut old_nodes: Vec<Option<MonteCarloNode>> = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .map(Some)
+            .collect();
+        let mut new_indices: Vec<Option<usize>> = vec![None; old_nodes.len()];
+        let mut order = vec![new_root];
+        let mut i = 0;
+        while i < order.len() {
+            let old_idx = order[i];
+            new_indices[old_idx] = Some(i);
+            if let Some(node) = &old_nodes[old_idx] {
+                order.extend(node.children.values().flatten());
+            }
+            i += 1;
         }
 
-        let all_plays = node.all_plays();
-
-        let best_play = all_plays
+        self.nodes = order
             .iter()
-            .max_by_key(|a| &self.nodes[node.child_node(**a)].n_plays);
+            .map(|&old_idx| {
+                let mut node = old_nodes[old_idx].take().expect("Each node has one parent");
+                node.own_idx = new_indices[old_idx].unwrap();
+                node.parent_idx = node.parent_idx.and_then(|parent| new_indices[parent]);
+                for child in node.children.values_mut().flatten() {
+                    *child = new_indices[*child].unwrap();
+                }
+                node
+            })
+            .collect();
+
+        // A root only counts the playouts below it, not the ones made from it as a leaf
+        let below: usize = self.nodes[0]
+            .children
+            .values()
+            .flatten()
+            .map(|&idx| self.nodes[idx].n_plays)
+            .sum();
+        let root = &mut self.nodes[0];
+        root.n_plays = below;
+        root.n_wins -= root.n_own_wins;
+        root.n_own_wins = 0;
+
+        if let Some(root) = &mut self.root_state {
+            root.do_move(play);
+        }
 
-        match best_play {
-            Some(play) => Ok(*play),
+        true
+    }
+
+    /// Whether the search has tried every move at the root. If not (e.g. the search was stopped
+    /// or had a small budget), `best_play` only picks among the moves it tried.
+    pub fn is_root_fully_expanded(&self) -> bool {
+        self.nodes
+            .first()
+            .is_some_and(|root| root.is_fully_expanded())
+    }
+
+    pub fn best_play(&mut self) -> Result<u32, &str> {
+        // Unexpanded plays are skipped, as a stopped search may not have tried every move
+        match self.root_moves().first() {
+            Some(best) => Ok(best.play),
             None => Err("No best play found. Was best_play called on a leaf node?"),
         }
     }
 
+    /// The root's expanded moves with their search results, most visited first.
+    pub fn root_moves(&self) -> Vec<MoveStats> {
+        let Some(root) = self.nodes.first() else {
+            return Vec::new();
+        };
+
+        let mut moves: Vec<MoveStats> = root
+            .children
+            .iter()
+            .filter_map(|(&play, &idx)| {
+                let node = &self.nodes[idx?];
+                Some(MoveStats {
+                    play,
+                    visits: node.n_plays,
+                    wins: node.n_wins,
+                })
+            })
+            .collect();
+        moves.sort_by(|a, b| b.visits.cmp(&a.visits).then(a.play.cmp(&b.play)));
+
+        moves
+    }
+
+    /// The line the search expects after `play` at the root: `play` followed by the most visited
+    /// move at each level, as far as the tree goes.
+    pub fn principal_variation(&self, play: u32) -> Vec<u32> {
+        let mut line = Vec::new();
+        let mut next = self
+            .nodes
+            .first()
+            .and_then(|root| Some((play, root.children.get(&play).copied()??)));
+
+        while let Some((play, idx)) = next {
+            line.push(play);
+            next = self.nodes[idx]
+                .children
+                .iter()
+                .filter_map(|(&play, &idx)| Some((play, idx?)))
+                .max_by_key(|&(play, idx)| (self.nodes[idx].n_plays, std::cmp::Reverse(play)));
+        }
+
+        line
+    }
+
+    /// The share of playouts below the root won by the player to move at the root, `None` before
+    /// any have finished.
+    pub fn win_rate(&self) -> Option<f64> {
+        let (wins, visits) = self.root_moves().iter().fold((0, 0), |(wins, visits), m| {
+            (wins + m.wins, visits + m.visits)
+        });
+
+        (visits > 0).then(|| wins as f64 / visits as f64)
+    }
+
     /// Phase 1, Selection: Select until not fully expanded OR leaf
     fn select(&mut self, state: &mut BoardState) -> usize {
         let mut node = &self.nodes[0];
@@ -209,7 +445,12 @@ impl MonteCarlo {
 
             match (player_to_win, winner) {
                 (Player::White, GameResult::PlayerAWon)
-                | (Player::Black, GameResult::PlayerBWon) => current_node.n_wins += 1,
+                | (Player::Black, GameResult::PlayerBWon) => {
+                    current_node.n_wins += 1;
+                    if current_node.own_idx == node_idx {
+                        current_node.n_own_wins += 1;
+                    }
+                }
                 _ => {}
             }
 
@@ -230,3 +471,13 @@ impl MonteCarlo {
         (root.n_wins, root.n_plays)
     }
 }
+
+fn same_position(a: &BoardState, b: &BoardState) -> bool {
+    a.player == b.player
+        && a.player_a_bit_board == b.player_a_bit_board
+        && a.player_b_bit_board == b.player_b_bit_board
+        && a.player_a_remaining == b.player_a_remaining
+        && a.player_b_remaining == b.player_b_remaining
+        && a.null_move_counter == b.null_move_counter
+        && a.start_position == b.start_position
+}
@@ -6,6 +6,8 @@ pub struct MonteCarloNode {
 
     pub n_plays: usize,
     pub n_wins: usize,
+    // The wins among the playouts started from this node itself rather than from a child
+    pub n_own_wins: usize,
 
     pub own_idx: usize,
     pub children: HashMap<u32, Option<usize>>,
@@ -22,6 +24,7 @@ impl MonteCarloNode {
             parent_idx,
             n_plays: 0,
             n_wins: 0,
+            n_own_wins: 0,
             own_idx: idx,
             children,
         }
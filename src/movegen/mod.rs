@@ -1,6 +1,10 @@
 #![allow(clippy::module_inception)]
 mod movegen;
 
+pub(crate) use movegen::count_cached_moves;
 pub use movegen::{
-    NULL_MOVE, PIECE_DATA, generate_moves, update_move_cache, update_move_cache_from_null_move,
+    CORNER_ATTACHERS_DATA, IllegalMove, Move, NULL_MOVE, ORIENTATION_DATA,
+    ORIENTATIONS_BITBOARD_DATA, ORIENTATIONS_BITBOARD_HALO_DATA, PIECE_DATA,
+    SHORT_BOUNDING_BOX_DATA, check_move, generate_first_moves, generate_moves, is_move_legal,
+    placements_for, rebuild_move_cache, update_move_cache, update_move_cache_from_null_move,
 };
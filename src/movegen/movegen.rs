@@ -1,5 +1,7 @@
 use std::collections::HashMap;
+use std::fmt;
 
+use crate::bitboard::Bitboard;
 use crate::board::{
     BoardState, Coord, CoordOffset, Player, StartPosition, get_start_position_coord,
 };
@@ -93,6 +95,31 @@ impl Move {
         ((packed & 0x10000) >> 16) as u8
     }
 
+    /// Decode a packed move from an untrusted source (e.g. a client), checking that it uses no
+    /// bits outside the packed layout and describes a real piece and orientation lying on the
+    /// board. Unlike `unpack`, the result is safe to pass to the table lookups in this module.
+    /// The null move is not a placement and is rejected as an invalid piece.
+    pub fn try_from_packed(packed: u32) -> Result<Move, IllegalMove> {
+        if packed >> 17 != 0 {
+            return Err(IllegalMove::InvalidEncoding);
+        }
+
+        let mov = Move::unpack(packed);
+        if mov.movetype as usize >= ORIENTATION_DATA.len() {
+            return Err(IllegalMove::InvalidPiece);
+        }
+        if mov.orientation as usize >= ORIENTATION_DATA[mov.movetype as usize].len() {
+            return Err(IllegalMove::InvalidOrientation);
+        }
+
+        let (bx, by) = SHORT_BOUNDING_BOX_DATA[mov.movetype as usize][mov.orientation as usize];
+        if mov.x + bx > 13 || mov.y + by > 13 {
+            return Err(IllegalMove::OutOfBounds);
+        }
+
+        Ok(mov)
+    }
+
     pub fn unpack(packed: u32) -> Move {
         Move {
             orientation: Self::get_orientation(packed),
@@ -104,6 +131,8 @@ impl Move {
     }
 }
 
+// Fast legality check for moves built by the move generator: the piece, orientation and location
+// are assumed to be valid, and corner contact isn't checked. Use `check_move` for anything else.
 pub fn is_move_legal(board: &BoardState, m: u32) -> bool {
     if m == NULL_MOVE {
         return true;
@@ -130,47 +159,22 @@ pub fn is_move_legal(board: &BoardState, m: u32) -> bool {
         return false;
     }
 
-    let my_bitboard = if player == 0 {
-        board.player_a_bit_board
+    let (my_bitboard, their_bitboard) = if player == 0 {
+        (&board.player_a_bit_board, &board.player_b_bit_board)
     } else {
-        board.player_b_bit_board
-    };
-
-    let their_bitboard = if player == 0 {
-        board.player_b_bit_board
-    } else {
-        board.player_a_bit_board
+        (&board.player_b_bit_board, &board.player_a_bit_board)
     };
 
     let piece_bitboard = &ORIENTATIONS_BITBOARD_DATA[movetype as usize][orientation as usize];
 
     // check for intersection or adjacency with my pieces
     let halo_data = &ORIENTATIONS_BITBOARD_HALO_DATA[movetype as usize][orientation as usize];
-
-    for bb_y in 0..piece_bitboard.len() + 2 {
-        if location.y as usize + bb_y == 0 || location.y as usize + bb_y > my_bitboard.len() {
-            continue;
-        }
-        let cached_halo = halo_data[bb_y] << location.x;
-        // shift by 1 to match the halo data
-        let game_row = my_bitboard[location.y as usize + bb_y - 1] << 1;
-        if (cached_halo & game_row) != 0 {
-            return false;
-        }
+    if my_bitboard.intersects_halo_rows_at(halo_data, location.x, location.y) {
+        return false;
     }
 
     // check if there's an intersection with opponent
-
-    for bb_y in 0..piece_bitboard.len() {
-        let bitboard_row = piece_bitboard[bb_y] << location.x;
-        let game_row = their_bitboard[location.y as usize + bb_y];
-
-        if bitboard_row & game_row != 0 {
-            return false;
-        }
-    }
-
-    true
+    !their_bitboard.intersects_rows_at(piece_bitboard, location.x, location.y)
 }
 
 pub fn is_move_blokee_legal(m: &Move) -> bool {
@@ -190,20 +194,155 @@ pub fn is_move_blokee_legal(m: &Move) -> bool {
     })
 }
 
+/// Why a move was rejected by `check_move`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IllegalMove {
+    InvalidEncoding,
+    GameOver,
+    WrongPlayer,
+    InvalidPiece,
+    InvalidOrientation,
+    OutOfBounds,
+    PieceAlreadyUsed,
+    OverlapsOwnTile,
+    TouchesOwnTile,
+    OverlapsOpponent,
+    NoCornerContact,
+    MissesStartSquare,
+    OutsideStartRegion,
+    PassWithMovesAvailable,
+}
+
+impl fmt::Display for IllegalMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            IllegalMove::InvalidEncoding => "the move sets bits outside the packed move layout",
+            IllegalMove::GameOver => "the game is already over",
+            IllegalMove::WrongPlayer => "it is not this player's turn",
+            IllegalMove::InvalidPiece => "there is no such piece",
+            IllegalMove::InvalidOrientation => "the piece has no such orientation",
+            IllegalMove::OutOfBounds => "the piece doesn't fit on the board",
+            IllegalMove::PieceAlreadyUsed => "the piece has already been placed",
+            IllegalMove::OverlapsOwnTile => "the piece overlaps one of the player's own tiles",
+            IllegalMove::TouchesOwnTile => "the piece shares an edge with the player's own tiles",
+            IllegalMove::OverlapsOpponent => "the piece overlaps the opponent's tiles",
+            IllegalMove::NoCornerContact => {
+                "the piece doesn't touch a corner of the player's tiles"
+            }
+            IllegalMove::MissesStartSquare => "the first piece must cover the start square",
+            IllegalMove::OutsideStartRegion => {
+                "the first piece must stay in the player's half of the board"
+            }
+            IllegalMove::PassWithMovesAvailable => "passing is only allowed with no moves left",
+        };
+
+        write!(f, "{}", reason)
+    }
+}
+
+impl std::error::Error for IllegalMove {}
+
+/// Check whether `m` can be played by the side to move, and if not, why. Unlike
+/// `is_move_legal`, this doesn't assume `m` came from the move generator: it checks the piece
+/// and orientation indices, the side to move, corner contact and the first-move rules, so a
+/// move passes exactly when `generate_moves` would produce it.
+pub fn check_move(board: &BoardState, m: u32) -> Result<(), IllegalMove> {
+    if m >> 17 != 0 {
+        return Err(IllegalMove::InvalidEncoding);
+    }
+
+    if board.is_game_over() {
+        return Err(IllegalMove::GameOver);
+    }
+
+    if m == NULL_MOVE {
+        return if generate_moves(board) == [NULL_MOVE] {
+            Ok(())
+        } else {
+            Err(IllegalMove::PassWithMovesAvailable)
+        };
+    }
+
+    if Move::get_player(m) != board.player as u8 {
+        return Err(IllegalMove::WrongPlayer);
+    }
+    let mov = Move::try_from_packed(m)?;
+
+    let (my_remaining, my_bitboard, their_bitboard) = if board.player == Player::White {
+        (
+            board.player_a_remaining,
+            &board.player_a_bit_board,
+            &board.player_b_bit_board,
+        )
+    } else {
+        (
+            board.player_b_remaining,
+            &board.player_b_bit_board,
+            &board.player_a_bit_board,
+        )
+    };
+
+    if my_remaining & (1 << mov.movetype) == 0 {
+        return Err(IllegalMove::PieceAlreadyUsed);
+    }
+
+    let mut piece = Bitboard::EMPTY;
+    piece.set_rows_at(
+        &ORIENTATIONS_BITBOARD_DATA[mov.movetype as usize][mov.orientation as usize],
+        mov.x,
+        mov.y,
+    );
+
+    if piece.intersects(my_bitboard) {
+        return Err(IllegalMove::OverlapsOwnTile);
+    }
+    if piece.intersects(&my_bitboard.edge_neighbours()) {
+        return Err(IllegalMove::TouchesOwnTile);
+    }
+    if piece.intersects(their_bitboard) {
+        return Err(IllegalMove::OverlapsOpponent);
+    }
+
+    if my_remaining == 0x1fffff {
+        let (start_a, start_b) = get_start_position_coord(board.start_position);
+        let start = if board.player == Player::White {
+            start_a
+        } else {
+            start_b
+        };
+
+        if !piece.get(start) {
+            return Err(IllegalMove::MissesStartSquare);
+        }
+
+        if board.start_position == StartPosition::MiddleBlokee && !is_move_blokee_legal(&mov) {
+            return Err(IllegalMove::OutsideStartRegion);
+        }
+    } else if !piece.intersects(&my_bitboard.diagonal_neighbours()) {
+        return Err(IllegalMove::NoCornerContact);
+    }
+
+    Ok(())
+}
+
 // Rules for the first move are different
 
 pub fn generate_first_moves(board: &BoardState) -> Vec<u32> {
-    // Get the starting position for the current player
+    if board.null_move_counter != 0 {
+        panic!("NMC not 0 at the beginning of the game");
+    }
+
+    first_moves(board, board.player)
+}
+
+fn first_moves(board: &BoardState, player: Player) -> Vec<u32> {
+    // Get the starting position for the player
     let (start_a, start_b) = get_start_position_coord(board.start_position);
-    let start_pos = match board.player {
+    let start_pos = match player {
         Player::White => start_a,
         Player::Black => start_b,
     };
 
-    if board.null_move_counter != 0 {
-        panic!("NMC not 0 at the beginning of the game");
-    }
-
     let mut moves: Vec<u32> = Vec::new();
 
     // each piece type
@@ -226,7 +365,7 @@ pub fn generate_first_moves(board: &BoardState) -> Vec<u32> {
                     y: piece_middle.y,
                     x: piece_middle.x,
                     movetype: piece as u8,
-                    player: board.player as u8,
+                    player: player as u8,
                     orientation: i as u8,
                 };
 
@@ -262,18 +401,33 @@ pub fn generate_moves(board: &BoardState) -> Vec<u32> {
         return vec![];
     }
 
-    let my_remaining = if board.player == Player::White {
+    let moves = placements_for(board, board.player);
+    if moves.is_empty() {
+        return vec![NULL_MOVE];
+    }
+
+    moves
+}
+
+/// The pieces `player` could place if it were their turn, without the null move. Both players'
+/// move caches are kept up to date, so this works for the side not to move too.
+pub fn placements_for(board: &BoardState, player: Player) -> Vec<u32> {
+    if board.is_game_over() {
+        return vec![];
+    }
+
+    let my_remaining = if player == Player::White {
         board.player_a_remaining
     } else {
         board.player_b_remaining
     };
 
     if my_remaining == 0x1fffff {
-        return generate_first_moves(board);
+        return first_moves(board, player);
     }
 
     // otherwise, use the cached moves
-    let my_corner_moves = if board.player == Player::White {
+    let my_corner_moves = if player == Player::White {
         &board.player_a_corner_moves
     } else {
         &board.player_b_corner_moves
@@ -283,14 +437,14 @@ pub fn generate_moves(board: &BoardState) -> Vec<u32> {
     unique_moves.sort_unstable();
     unique_moves.dedup();
 
-    if unique_moves.is_empty() {
-        return vec![NULL_MOVE];
-    }
-
     unique_moves
 }
 
 pub fn get_legal_moves_from(from: Coord, movetype: u8, board: &BoardState) -> Vec<u32> {
+    legal_moves_from(from, movetype, board.player, board)
+}
+
+fn legal_moves_from(from: Coord, movetype: u8, player: Player, board: &BoardState) -> Vec<u32> {
     let mut legal_moves: Vec<u32> = Vec::new();
     let orientation_data = &ORIENTATION_DATA[movetype as usize];
 
@@ -314,7 +468,7 @@ pub fn get_legal_moves_from(from: Coord, movetype: u8, board: &BoardState) -> Ve
                 orientation: i as u8,
                 y: coord.y,
                 x: coord.x,
-                player: board.player as u8,
+                player: player as u8,
                 movetype,
             };
 
@@ -344,17 +498,23 @@ pub fn update_move_cache(board: &mut BoardState, last_move: u32) {
     };
 
     // update bitboards
-    let piece_bitboard =
-        &ORIENTATIONS_BITBOARD_DATA[mov.movetype as usize][mov.orientation as usize];
-
-    for bb_y in 0..piece_bitboard.len() {
-        my_bitboard[mov.y as usize + bb_y] |= piece_bitboard[bb_y] << mov.x;
-    }
+    my_bitboard.set_rows_at(
+        &ORIENTATIONS_BITBOARD_DATA[mov.movetype as usize][mov.orientation as usize],
+        mov.x,
+        mov.y,
+    );
 
     // Update the corner data.
     // 1. For each of the corners of the placed piece, clear the corner moves for that corner (because there cannot be any moves there anymore)
-    // 2. Filter out the moves that are no longer valid
-    // 3. Add the new moves to the corner moves
+    // 2. Add the new moves to the corner moves
+    // 3. Filter out the moves that are no longer valid
+    // The caches are taken out of the board meanwhile, so the legality checks can borrow it.
+
+    let mover = board.player;
+    let mut player_a_moves = std::mem::take(&mut board.player_a_corner_moves);
+    let mut player_b_moves = std::mem::take(&mut board.player_b_corner_moves);
+    // the mover's corners generated by this move, which are exact already
+    let mut fresh_corners = Bitboard::EMPTY;
 
     let corners = &CORNERS_DATA[mov.movetype as usize][mov.orientation as usize];
     for corner in corners {
@@ -364,15 +524,18 @@ pub fn update_move_cache(board: &mut BoardState, last_move: u32) {
         };
 
         // delete all the moves for this corner
-        board.player_a_corner_moves.remove(&absolute_corner);
-        board.player_b_corner_moves.remove(&absolute_corner);
+        player_a_moves.remove(&absolute_corner);
+        player_b_moves.remove(&absolute_corner);
+        board.player_a_live_corners.clear(absolute_corner);
+        board.player_b_live_corners.clear(absolute_corner);
     }
 
-    let my_remaining_pieces = if board.player == Player::White {
-        board.player_a_remaining
+    let (my_corner_moves, my_remaining_pieces) = if mover == Player::White {
+        (&mut player_a_moves, board.player_a_remaining)
     } else {
-        board.player_b_remaining
+        (&mut player_b_moves, board.player_b_remaining)
     };
+    let mut my_live_corners = *board.live_corners(mover);
 
     let corner_attachers = &CORNER_ATTACHERS_DATA[mov.movetype as usize][mov.orientation as usize];
     for corner in corner_attachers {
@@ -385,15 +548,7 @@ pub fn update_move_cache(board: &mut BoardState, last_move: u32) {
             y: (corner.y + mov.y as i8) as u8,
         };
 
-        if !absolute_corner.in_bounds() {
-            continue;
-        }
-
-        if board.player == Player::White {
-            if board.player_a_corner_moves.contains_key(&absolute_corner) {
-                continue;
-            }
-        } else if board.player_b_corner_moves.contains_key(&absolute_corner) {
+        if !absolute_corner.in_bounds() || my_corner_moves.contains_key(&absolute_corner) {
             continue;
         }
 
@@ -409,68 +564,193 @@ pub fn update_move_cache(board: &mut BoardState, last_move: u32) {
             legal_moves.extend(get_legal_moves_from(absolute_corner, movetype, board));
         }
 
-        if board.player == Player::White {
-            board
-                .player_a_corner_moves
-                .insert(absolute_corner, legal_moves);
-        } else {
-            board
-                .player_b_corner_moves
-                .insert(absolute_corner, legal_moves);
+        if !legal_moves.is_empty() {
+            my_live_corners.set(absolute_corner);
         }
+        my_corner_moves.insert(absolute_corner, legal_moves);
+        fresh_corners.set(absolute_corner);
     }
 
+    if mover == Player::White {
+        board.player_a_live_corners = my_live_corners;
+    } else {
+        board.player_b_live_corners = my_live_corners;
+    }
     board.skip_turn();
 
-    // filter opponent's moves (now the player to move)
-    let opponent_cached_moves: Vec<Coord> = if board.player == Player::White {
-        board.player_a_corner_moves.keys().cloned().collect()
+    // Filter out the cached moves the new piece made illegal, for both players: the opponent's
+    // (now the player to move) can't overlap it, and the mover's can't touch it along an edge or
+    // use the same piece. Keeping both caches exact lets mobility be read off for either side.
+    // Only moves whose bounding box reaches the new piece can have changed, so the rest of the
+    // cache isn't rechecked.
+    let (player_a_fresh, player_b_fresh) = if mover == Player::White {
+        (fresh_corners, Bitboard::EMPTY)
     } else {
-        board.player_b_corner_moves.keys().cloned().collect()
+        (Bitboard::EMPTY, fresh_corners)
     };
 
-    for coord in opponent_cached_moves {
-        let old_moves = if board.player == Player::White {
-            board.player_a_corner_moves.get_mut(&coord).unwrap().clone()
-        } else {
-            board.player_b_corner_moves.get_mut(&coord).unwrap().clone()
-        };
-
-        let new_moves: Vec<u32> = old_moves
-            .into_iter()
-            .filter(|m| is_move_legal(board, *m))
-            .collect();
-
-        if board.player == Player::White {
-            board.player_a_corner_moves.insert(coord, new_moves);
-        } else {
-            board.player_b_corner_moves.insert(coord, new_moves);
-        }
-    }
+    let mut player_a_live_corners = board.player_a_live_corners;
+    filter_corner_moves(
+        board,
+        &mut player_a_moves,
+        &mut player_a_live_corners,
+        &player_a_fresh,
+        |m| may_be_affected(m, &mov, mover == Player::White),
+    );
+
+    let mut player_b_live_corners = board.player_b_live_corners;
+    filter_corner_moves(
+        board,
+        &mut player_b_moves,
+        &mut player_b_live_corners,
+        &player_b_fresh,
+        |m| may_be_affected(m, &mov, mover == Player::Black),
+    );
+
+    board.player_a_live_corners = player_a_live_corners;
+    board.player_b_live_corners = player_b_live_corners;
+    board.player_a_corner_moves = player_a_moves;
+    board.player_b_corner_moves = player_b_moves;
 }
 
+/// Filter the cached moves of the player to move. `do_move` keeps both caches exact, so this
+/// is only needed for caches that were changed by hand.
 pub fn update_move_cache_from_null_move(board: &mut BoardState) {
     // Take ownership of the cached moves, filter them, then reassign
-    let cached_moves = if board.player == Player::White {
+    let mut cached_moves = if board.player == Player::White {
         std::mem::take(&mut board.player_a_corner_moves)
     } else {
         std::mem::take(&mut board.player_b_corner_moves)
     };
 
-    let filtered_moves: HashMap<Coord, Vec<u32>> = cached_moves
-        .into_iter()
-        .map(|(coord, moves)| {
-            let legal_moves: Vec<u32> = moves
-                .into_iter()
-                .filter(|m| is_move_legal(board, *m))
-                .collect();
-            (coord, legal_moves)
-        })
-        .collect();
+    let mut live_corners = *board.live_corners(board.player);
+    filter_corner_moves(
+        board,
+        &mut cached_moves,
+        &mut live_corners,
+        &Bitboard::EMPTY,
+        |_| true,
+    );
 
     if board.player == Player::White {
-        board.player_a_corner_moves = filtered_moves;
+        board.player_a_corner_moves = cached_moves;
+        board.player_a_live_corners = live_corners;
     } else {
-        board.player_b_corner_moves = filtered_moves;
+        board.player_b_corner_moves = cached_moves;
+        board.player_b_live_corners = live_corners;
     }
 }
+
+// Whether placing `placed` could have made the cached move `m` illegal. Moves of the mover
+// can lose their piece or touch the new one along an edge, so their bounding box is checked
+// against the new piece's grown by a square; the opponent's can only overlap it.
+fn may_be_affected(m: u32, placed: &Move, same_player: bool) -> bool {
+    let movetype = Move::get_movetype(m);
+    if same_player && movetype == placed.movetype {
+        return true;
+    }
+
+    let location = Move::get_location(m);
+    let (bx, by) = SHORT_BOUNDING_BOX_DATA[movetype as usize][Move::get_orientation(m) as usize];
+    let (px, py) = SHORT_BOUNDING_BOX_DATA[placed.movetype as usize][placed.orientation as usize];
+    let margin = u8::from(same_player);
+
+    location.x <= placed.x + px + margin
+        && location.x + bx + margin >= placed.x
+        && location.y <= placed.y + py + margin
+        && location.y + by + margin >= placed.y
+}
+
+// Drop the cached moves that are no longer legal, and the corners left without moves from
+// `live_corners`
+fn filter_corner_moves(
+    board: &BoardState,
+    corner_moves: &mut HashMap<Coord, Vec<u32>>,
+    live_corners: &mut Bitboard,
+    skipped: &Bitboard,
+    may_be_affected: impl Fn(u32) -> bool,
+) {
+    for (&coord, moves) in corner_moves.iter_mut() {
+        if skipped.get(coord) || moves.is_empty() {
+            continue;
+        }
+
+        moves.retain(|&m| !may_be_affected(m) || is_move_legal(board, m));
+
+        if moves.is_empty() {
+            live_corners.clear(coord);
+        }
+    }
+}
+
+/// How many distinct moves a corner move cache holds. A legal move is in the list of every live
+/// corner one of its own corner squares sits on, so it is counted at the first of them.
+pub(crate) fn count_cached_moves(
+    corner_moves: &HashMap<Coord, Vec<u32>>,
+    live_corners: &Bitboard,
+) -> usize {
+    let first_live_corner = |m: u32| {
+        let mov = Move::unpack(m);
+        CORNERS_DATA[mov.movetype as usize][mov.orientation as usize]
+            .iter()
+            .map(|c| Coord {
+                x: c.x + mov.x,
+                y: c.y + mov.y,
+            })
+            .find(|&c| live_corners.get(c))
+    };
+
+    corner_moves
+        .iter()
+        .map(|(&coord, moves)| {
+            moves
+                .iter()
+                .filter(|&&m| first_live_corner(m) == Some(coord))
+                .count()
+        })
+        .sum()
+}
+
+fn live_corners_from_scratch(corner_moves: &HashMap<Coord, Vec<u32>>) -> Bitboard {
+    Bitboard::from_squares(
+        corner_moves
+            .iter()
+            .filter(|(_, moves)| !moves.is_empty())
+            .map(|(&coord, _)| coord),
+    )
+}
+
+// Build a player's corner move cache from scratch, for boards that weren't reached through
+// `update_move_cache` (e.g. positions set up from placements or bitboards). Each empty square
+// that touches one of the player's tiles diagonally but none along an edge is a corner.
+fn corner_moves_from_scratch(board: &BoardState, player: Player) -> HashMap<Coord, Vec<u32>> {
+    let (my_bitboard, my_remaining) = if player == Player::White {
+        (&board.player_a_bit_board, board.player_a_remaining)
+    } else {
+        (&board.player_b_bit_board, board.player_b_remaining)
+    };
+
+    let mut corner_moves: HashMap<Coord, Vec<u32>> = HashMap::new();
+
+    for corner in my_bitboard.corners(&board.occupied()).squares() {
+        let mut legal_moves: Vec<u32> = Vec::new();
+        for unplaced_piece in 0..21 {
+            if my_remaining & (1 << unplaced_piece) == 0 {
+                continue;
+            }
+
+            legal_moves.extend(legal_moves_from(corner, unplaced_piece, player, board));
+        }
+
+        corner_moves.insert(corner, legal_moves);
+    }
+
+    corner_moves
+}
+
+pub fn rebuild_move_cache(board: &mut BoardState) {
+    board.player_a_corner_moves = corner_moves_from_scratch(board, Player::White);
+    board.player_b_corner_moves = corner_moves_from_scratch(board, Player::Black);
+    board.player_a_live_corners = live_corners_from_scratch(&board.player_a_corner_moves);
+    board.player_b_live_corners = live_corners_from_scratch(&board.player_b_corner_moves);
+}
@@ -0,0 +1,390 @@
+//! Text notation for positions.
+//!
+//! A position is written as six space-separated fields, similar to chess FEN:
+//!
+//! ```text
+//! <grid> <placed a> <placed b> <side to move> <null moves> <start position>
+//! ```
+//!
+//! - `grid`: the 14 rows of the board from `y = 0` down to `y = 13`, separated by `/`. Within a
+//!   row, `a` is a tile of player A (white), `b` a tile of player B (black), and a number is a
+//!   run of that many empty squares, going from `x = 0` to `x = 13`.
+//! - `placed a`, `placed b`: the pieces each player has placed, as a hexadecimal bitmask over
+//!   the 21 piece indices (the complement of `player_x_remaining`).
+//! - `side to move`: `a` or `b`.
+//! - `null moves`: the number of passes in a row, `0` to `2`.
+//! - `start position`: `corner`, `middle` or `middleBlokee`.
+//!
+//! The empty Corner board is `14/14/14/14/14/14/14/14/14/14/14/14/14/14 0 0 a 0 corner`.
+//!
+//! Moves are written as `<piece>-<orientation>@<square>`, e.g. `L5-3@e7`. The piece is one of
+//! the names in [`PIECE_NAMES`], the orientation indexes that piece's orientations, and the
+//! square is the top-left corner of the piece's bounding box: a file letter `a`-`n` for `x`
+//! followed by a rank `1`-`14` for `y + 1`. A null move is written `pass`.
+
+use std::fmt;
+
+use crate::bitboard::Bitboard;
+use crate::board::{BoardState, Coord, Player, PositionError, StartPosition};
+use crate::movegen::{IllegalMove, Move, NULL_MOVE, ORIENTATION_DATA, check_move};
+
+const ALL_PIECES: u32 = 0x1fffff;
+
+/// Names of the pieces, indexed by movetype.
+pub const PIECE_NAMES: [&str; 21] = [
+    "L5", "Y5", "N5", "V3", "U5", "V5", "Z5", "X5", "T5", "W5", "P5", "F5", "O4", "L4", "T4", "Z4",
+    "I1", "I2", "I3", "I4", "I5",
+];
+
+pub fn piece_from_name(name: &str) -> Option<u8> {
+    PIECE_NAMES
+        .iter()
+        .position(|&piece| piece.eq_ignore_ascii_case(name))
+        .map(|piece| piece as u8)
+}
+
+/// Write a square as `<file><rank>`, e.g. `e7` for x = 4, y = 6.
+pub fn square_name(coord: Coord) -> String {
+    format!("{}{}", (b'a' + coord.x) as char, coord.y + 1)
+}
+
+pub fn parse_square(square: &str) -> Option<Coord> {
+    let mut chars = square.chars();
+    let file = chars.next()?.to_ascii_lowercase();
+    if !file.is_ascii_lowercase() {
+        return None;
+    }
+
+    let rank = chars.as_str();
+    if rank.starts_with('0') || rank.starts_with('+') {
+        return None;
+    }
+    let rank: u8 = rank.parse().ok()?;
+
+    let coord = Coord {
+        x: file as u8 - b'a',
+        y: rank.checked_sub(1)?,
+    };
+    if !coord.in_bounds() {
+        return None;
+    }
+
+    Some(coord)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotationError {
+    FieldCount(usize),
+    RowCount(usize),
+    InvalidRow(usize),
+    InvalidPieceMask(String),
+    InvalidSideToMove(String),
+    InvalidNullMoveCounter(String),
+    InvalidStartPosition(String),
+    Position(PositionError),
+}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotationError::FieldCount(count) => write!(f, "expected 6 fields, found {}", count),
+            NotationError::RowCount(count) => write!(f, "expected 14 rows, found {}", count),
+            NotationError::InvalidRow(y) => write!(f, "row {} doesn't describe 14 squares", y),
+            NotationError::InvalidPieceMask(mask) => write!(f, "invalid piece mask '{}'", mask),
+            NotationError::InvalidSideToMove(side) => write!(f, "invalid side to move '{}'", side),
+            NotationError::InvalidNullMoveCounter(counter) => {
+                write!(f, "invalid null move counter '{}'", counter)
+            }
+            NotationError::InvalidStartPosition(name) => {
+                write!(f, "invalid start position '{}'", name)
+            }
+            NotationError::Position(e) => write!(f, "illegal position: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for NotationError {}
+
+impl From<PositionError> for NotationError {
+    fn from(e: PositionError) -> Self {
+        NotationError::Position(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoveNotationError {
+    Malformed(String),
+    UnknownPiece(String),
+    InvalidOrientation {
+        piece: u8,
+        orientation: String,
+    },
+    InvalidSquare(String),
+    Illegal {
+        notation: String,
+        reason: IllegalMove,
+    },
+}
+
+impl fmt::Display for MoveNotationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveNotationError::Malformed(m) => {
+                write!(
+                    f,
+                    "'{}' is not of the form <piece>-<orientation>@<square>",
+                    m
+                )
+            }
+            MoveNotationError::UnknownPiece(piece) => write!(f, "unknown piece '{}'", piece),
+            MoveNotationError::InvalidOrientation { piece, orientation } => write!(
+                f,
+                "{} has no orientation '{}'",
+                PIECE_NAMES[*piece as usize], orientation
+            ),
+            MoveNotationError::InvalidSquare(square) => write!(f, "invalid square '{}'", square),
+            MoveNotationError::Illegal { notation, reason } => {
+                write!(f, "{} is not a legal move: {}", notation, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MoveNotationError {}
+
+fn write_row(out: &mut String, row_a: u16, row_b: u16) {
+    let mut empty_run = 0;
+    for x in 0..14 {
+        let tile = if row_a & (1 << x) != 0 {
+            Some('a')
+        } else if row_b & (1 << x) != 0 {
+            Some('b')
+        } else {
+            None
+        };
+
+        match tile {
+            Some(c) => {
+                if empty_run > 0 {
+                    out.push_str(&empty_run.to_string());
+                    empty_run = 0;
+                }
+                out.push(c);
+            }
+            None => empty_run += 1,
+        }
+    }
+
+    if empty_run > 0 {
+        out.push_str(&empty_run.to_string());
+    }
+}
+
+fn parse_row(row: &str, y: usize) -> Result<(u16, u16), NotationError> {
+    let mut row_a = 0u16;
+    let mut row_b = 0u16;
+    let mut x = 0usize;
+    let mut chars = row.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            'a' | 'b' => {
+                if x >= 14 {
+                    return Err(NotationError::InvalidRow(y));
+                }
+                if c == 'a' {
+                    row_a |= 1 << x;
+                } else {
+                    row_b |= 1 << x;
+                }
+                x += 1;
+            }
+            '1'..='9' => {
+                let mut run = c.to_digit(10).unwrap() as usize;
+                while let Some(digit) = chars.peek().and_then(|d| d.to_digit(10)) {
+                    run = run * 10 + digit as usize;
+                    chars.next();
+                    if run > 14 {
+                        return Err(NotationError::InvalidRow(y));
+                    }
+                }
+                x += run;
+            }
+            _ => return Err(NotationError::InvalidRow(y)),
+        }
+    }
+
+    if x != 14 {
+        return Err(NotationError::InvalidRow(y));
+    }
+
+    Ok((row_a, row_b))
+}
+
+fn parse_placed(field: &str) -> Result<u32, NotationError> {
+    match u32::from_str_radix(field, 16) {
+        Ok(placed) if placed & !ALL_PIECES == 0 && !field.starts_with('+') => Ok(placed),
+        _ => Err(NotationError::InvalidPieceMask(field.to_string())),
+    }
+}
+
+impl BoardState {
+    /// Write the position in the notation described in the [`crate::notation`] module docs.
+    pub fn to_notation(&self) -> String {
+        let mut out = String::new();
+
+        for y in 0..14 {
+            if y > 0 {
+                out.push('/');
+            }
+            write_row(
+                &mut out,
+                self.player_a_bit_board[y],
+                self.player_b_bit_board[y],
+            );
+        }
+
+        let side = match self.player {
+            Player::White => 'a',
+            Player::Black => 'b',
+        };
+
+        out.push_str(&format!(
+            " {:x} {:x} {} {} {}",
+            !self.player_a_remaining & ALL_PIECES,
+            !self.player_b_remaining & ALL_PIECES,
+            side,
+            self.null_move_counter,
+            self.start_position.name()
+        ));
+
+        out
+    }
+
+    /// Read a position written by [`BoardState::to_notation`]. The position is validated like
+    /// [`BoardState::from_bitboards`], and its move caches are rebuilt.
+    pub fn from_notation(notation: &str) -> Result<BoardState, NotationError> {
+        let fields: Vec<&str> = notation.split_whitespace().collect();
+        if fields.len() != 6 {
+            return Err(NotationError::FieldCount(fields.len()));
+        }
+
+        let rows: Vec<&str> = fields[0].split('/').collect();
+        if rows.len() != 14 {
+            return Err(NotationError::RowCount(rows.len()));
+        }
+
+        let mut player_a_bit_board = Bitboard::EMPTY;
+        let mut player_b_bit_board = Bitboard::EMPTY;
+        for (y, row) in rows.iter().enumerate() {
+            (player_a_bit_board[y], player_b_bit_board[y]) = parse_row(row, y)?;
+        }
+
+        let player_a_placed = parse_placed(fields[1])?;
+        let player_b_placed = parse_placed(fields[2])?;
+
+        let player = match fields[3] {
+            "a" => Player::White,
+            "b" => Player::Black,
+            side => return Err(NotationError::InvalidSideToMove(side.to_string())),
+        };
+
+        let null_move_counter = match fields[4].parse::<u8>() {
+            Ok(counter) if counter <= 2 && !fields[4].starts_with('+') => counter,
+            _ => return Err(NotationError::InvalidNullMoveCounter(fields[4].to_string())),
+        };
+
+        let start_position = StartPosition::from_name(fields[5])
+            .ok_or_else(|| NotationError::InvalidStartPosition(fields[5].to_string()))?;
+
+        let mut board = BoardState::from_bitboards(
+            start_position,
+            player_a_bit_board,
+            player_b_bit_board,
+            !player_a_placed & ALL_PIECES,
+            !player_b_placed & ALL_PIECES,
+            player,
+        )?;
+        board.null_move_counter = null_move_counter;
+
+        Ok(board)
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let piece = PIECE_NAMES
+            .get(self.movetype as usize)
+            .copied()
+            .unwrap_or("??");
+        let square = Coord {
+            x: self.x,
+            y: self.y,
+        };
+
+        write!(f, "{}-{}@{}", piece, self.orientation, square_name(square))
+    }
+}
+
+impl Move {
+    /// Write a packed move in move notation, e.g. `L5-3@e7`, or `pass` for the null move.
+    pub fn to_notation(packed: u32) -> String {
+        if packed == NULL_MOVE {
+            return "pass".to_string();
+        }
+
+        Move::unpack(packed).to_string()
+    }
+
+    /// Parse a move in move notation for `player`, checking only that it names a real piece,
+    /// orientation and square.
+    pub fn parse_notation(notation: &str, player: Player) -> Result<u32, MoveNotationError> {
+        let notation = notation.trim();
+        if notation.eq_ignore_ascii_case("pass") {
+            return Ok(NULL_MOVE);
+        }
+
+        let malformed = || MoveNotationError::Malformed(notation.to_string());
+        let (piece, rest) = notation.split_once('-').ok_or_else(malformed)?;
+        let (orientation, square) = rest.split_once('@').ok_or_else(malformed)?;
+
+        let movetype =
+            piece_from_name(piece).ok_or(MoveNotationError::UnknownPiece(piece.to_string()))?;
+
+        let orientation_count = ORIENTATION_DATA[movetype as usize].len();
+        let orientation = match orientation.parse::<u8>() {
+            Ok(o) if (o as usize) < orientation_count && !orientation.starts_with('+') => o,
+            _ => {
+                return Err(MoveNotationError::InvalidOrientation {
+                    piece: movetype,
+                    orientation: orientation.to_string(),
+                });
+            }
+        };
+
+        let coord =
+            parse_square(square).ok_or(MoveNotationError::InvalidSquare(square.to_string()))?;
+
+        Ok(Move {
+            orientation,
+            y: coord.y,
+            x: coord.x,
+            movetype,
+            player: player as u8,
+        }
+        .pack())
+    }
+
+    /// Parse a move in move notation for the side to move, rejecting it unless it is legal in
+    /// `board`.
+    pub fn from_notation(notation: &str, board: &BoardState) -> Result<u32, MoveNotationError> {
+        let packed = Move::parse_notation(notation, board.player)?;
+
+        check_move(board, packed).map_err(|reason| MoveNotationError::Illegal {
+            notation: notation.trim().to_string(),
+            reason,
+        })?;
+
+        Ok(packed)
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::{BoardState, GameResult, Score, StartPosition};
+use crate::movegen::generate_moves;
+use crate::notation::NotationError;
+
+/// Statistics of the engine search that chose a move, taken at the root.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchStats {
+    pub plays: usize,
+    pub wins: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveInfo {
+    /// When the move was played, in milliseconds since the Unix epoch (0 if unknown)
+    pub timestamp_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub think_time_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search: Option<SearchStats>,
+}
+
+/// A complete or partial game: where it started, free-form metadata (player names, engine
+/// settings, dates, ...) and the packed moves played, including null moves.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct GameRecord {
+    pub start_position: StartPosition,
+    /// Position the moves start from, in `BoardState::to_notation` form, for games that don't
+    /// begin on an empty board.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub initial_position: Option<String>,
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    pub moves: Vec<u32>,
+    /// Per-move annotations, parallel to `moves`. Empty when nothing beyond the moves is known.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub move_info: Vec<MoveInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<GameResult>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<Score>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    InitialPosition(NotationError),
+    /// A move that isn't legal in the position it was played in.
+    IllegalMove {
+        ply: usize,
+        mov: u32,
+    },
+    /// The moves are all legal, but don't lead to the board they were recorded with.
+    BoardMismatch,
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::InitialPosition(e) => write!(f, "invalid initial position: {}", e),
+            ReplayError::IllegalMove { ply, mov } => {
+                write!(f, "illegal move {} at ply {}", mov, ply)
+            }
+            ReplayError::BoardMismatch => {
+                write!(f, "the moves don't lead to the recorded position")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl GameRecord {
+    pub fn new(start_position: StartPosition) -> Self {
+        Self {
+            start_position,
+            initial_position: None,
+            metadata: BTreeMap::new(),
+            moves: Vec::new(),
+            move_info: Vec::new(),
+            result: None,
+            score: None,
+        }
+    }
+
+    /// The position before the first move.
+    pub fn initial_board(&self) -> Result<BoardState, ReplayError> {
+        match &self.initial_position {
+            Some(notation) => {
+                BoardState::from_notation(notation).map_err(ReplayError::InitialPosition)
+            }
+            None => Ok(BoardState::new(self.start_position)),
+        }
+    }
+
+    /// Play all the moves from the initial position, checking each one is legal.
+    pub fn replay(&self) -> Result<BoardState, ReplayError> {
+        let mut board = self.initial_board()?;
+
+        for (ply, &mov) in self.moves.iter().enumerate() {
+            if !generate_moves(&board).contains(&mov) {
+                return Err(ReplayError::IllegalMove { ply, mov });
+            }
+            board.do_move(mov);
+        }
+
+        Ok(board)
+    }
+
+    /// Fill in `result` and `score` from a board reached by playing this record.
+    pub fn set_outcome(&mut self, board: &BoardState) {
+        self.result = Some(board.game_result());
+        self.score = Some(board.score());
+    }
+}
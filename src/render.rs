@@ -0,0 +1,169 @@
+//! Text rendering of a `BoardState` for terminals, logs and test failures.
+//!
+//! Squares are drawn as `A`/`B` for placed tiles, `*` for an uncovered start square, `a`/`b` for
+//! squares where only that player has cached corner moves, `+` where both do, and `.` otherwise.
+//! Rows and columns are labelled as in the move notation.
+//!
+//! With the territory overlay (see [`crate::analysis`]), the remaining empty squares are drawn as
+//! `-` if only player A can reach them, `=` if only player B can, `x` if neither can, and `.` if
+//! both can.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::analysis::{Territory, territory};
+use crate::board::{BoardState, Coord, Player, get_start_position_coord};
+use crate::notation::PIECE_NAMES;
+
+const RESET: &str = "\x1b[0m";
+const PLAYER_A_TILE: &str = "\x1b[30;44m";
+const PLAYER_B_TILE: &str = "\x1b[30;43m";
+const PLAYER_A_CORNER: &str = "\x1b[34m";
+const PLAYER_B_CORNER: &str = "\x1b[33m";
+const SHARED_CORNER: &str = "\x1b[35m";
+const START_SQUARE: &str = "\x1b[1;31m";
+const EMPTY: &str = "\x1b[90m";
+const DEAD: &str = "\x1b[2;90m";
+
+/// Renders a board, optionally with ANSI colours. Created by `BoardState::display_ansi`;
+/// `BoardState`'s own `Display` impl renders without colour.
+pub struct BoardDisplay<'a> {
+    board: &'a BoardState,
+    colour: bool,
+    territory: Option<Territory>,
+}
+
+impl BoardDisplay<'_> {
+    /// Overlay the board's territory analysis on the empty squares.
+    pub fn with_territory(mut self) -> Self {
+        self.territory = Some(territory(self.board));
+        self
+    }
+}
+
+impl BoardState {
+    pub fn display_ansi(&self) -> BoardDisplay<'_> {
+        BoardDisplay {
+            board: self,
+            colour: true,
+            territory: None,
+        }
+    }
+
+    /// Render without colour, with the territory overlay.
+    pub fn display_territory(&self) -> BoardDisplay<'_> {
+        BoardDisplay {
+            board: self,
+            colour: false,
+            territory: None,
+        }
+        .with_territory()
+    }
+}
+
+impl fmt::Display for BoardState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        BoardDisplay {
+            board: self,
+            colour: false,
+            territory: None,
+        }
+        .fmt(f)
+    }
+}
+
+fn has_live_corner(corner_moves: &HashMap<Coord, Vec<u32>>, c: Coord) -> bool {
+    corner_moves.get(&c).is_some_and(|moves| !moves.is_empty())
+}
+
+fn remaining_names(remaining: u32) -> String {
+    let names: Vec<&str> = (0..21)
+        .filter(|piece| remaining & (1 << piece) != 0)
+        .map(|piece| PIECE_NAMES[piece])
+        .collect();
+
+    if names.is_empty() {
+        "-".to_string()
+    } else {
+        names.join(" ")
+    }
+}
+
+impl fmt::Display for BoardDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let board = self.board;
+        let (start_a, start_b) = get_start_position_coord(board.start_position);
+
+        write!(f, "   ")?;
+        for x in 0..14u8 {
+            write!(f, " {}", (b'a' + x) as char)?;
+        }
+        writeln!(f)?;
+
+        for y in 0..14u8 {
+            write!(f, "{:>3}", y + 1)?;
+            for x in 0..14u8 {
+                let c = Coord { x, y };
+                let (symbol, colour) = if board.player_a_bit_board.get(c) {
+                    ('A', PLAYER_A_TILE)
+                } else if board.player_b_bit_board.get(c) {
+                    ('B', PLAYER_B_TILE)
+                } else if c == start_a || c == start_b {
+                    ('*', START_SQUARE)
+                } else {
+                    match (
+                        has_live_corner(&board.player_a_corner_moves, c),
+                        has_live_corner(&board.player_b_corner_moves, c),
+                    ) {
+                        (true, true) => ('+', SHARED_CORNER),
+                        (true, false) => ('a', PLAYER_A_CORNER),
+                        (false, true) => ('b', PLAYER_B_CORNER),
+                        (false, false) => match &self.territory {
+                            Some(territory) => {
+                                match (territory.player_a.get(c), territory.player_b.get(c)) {
+                                    (true, true) => ('.', EMPTY),
+                                    (true, false) => ('-', PLAYER_A_CORNER),
+                                    (false, true) => ('=', PLAYER_B_CORNER),
+                                    (false, false) => ('x', DEAD),
+                                }
+                            }
+                            None => ('.', EMPTY),
+                        },
+                    }
+                };
+
+                if self.colour {
+                    write!(f, " {}{}{}", colour, symbol, RESET)?;
+                } else {
+                    write!(f, " {}", symbol)?;
+                }
+            }
+            writeln!(f)?;
+        }
+
+        let score = board.score();
+        let to_move = match board.player {
+            Player::White => "A",
+            Player::Black => "B",
+        };
+        writeln!(
+            f,
+            "{} to move, score A {} - B {}, {} null move(s), {}",
+            to_move,
+            score.player_a,
+            score.player_b,
+            board.null_move_counter,
+            board.start_position.name()
+        )?;
+        writeln!(
+            f,
+            "A remaining: {}",
+            remaining_names(board.player_a_remaining)
+        )?;
+        write!(
+            f,
+            "B remaining: {}",
+            remaining_names(board.player_b_remaining)
+        )
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::mcts::SearchBudget;
+use crate::server::log::LogLevel;
+
+pub const USAGE: &str = "\
+Usage: blok-rs [OPTIONS]
+
+Options:
+  --config <FILE>             Read settings from a JSON file; flags given alongside override it
+  --bind <ADDRESS>            Address to listen on [default: 127.0.0.1]
+  --port <PORT>               Port to listen on [default: 8080]
+  --threads <N>               Threads handling connections [default: one per core]
+  --search-threads <N>        Searches running at once across all connections [default: one per core]
+  --budget <DIFFICULTY>=<N>   Search budget for a difficulty, in iterations, or in milliseconds
+                              with an `ms` suffix, e.g. `hard=200000` or `hard=1500ms`. Repeatable
+  --max-sessions <N>          Most connections served at once [default: no limit]
+  --session-timeout <SECS>    How long a game is kept once nobody uses it, e.g. for a closed
+                              connection to resume it [default: 600]
+  --max-games <N>             Most games kept at once, open or waiting to be resumed
+                              [default: 1000]
+  --max-games-per-connection <N>
+                              Most games open on one connection [default: 8]
+  --log-level <LEVEL>         off, error, warn, info or debug [default: info]
+  -h, --help                  Print this help
+";
+
+/// The server's settings. The config file has the same fields, in camelCase, with budgets written
+/// as `{\"iterations\": 200000}` or `{\"millis\": 1500}`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: String,
+    pub port: u16,
+    /// Threads for the async runtime handling connections, one per core if `None`. Searches run
+    /// on blocking threads of their own.
+    pub worker_threads: Option<usize>,
+    /// How many searches may run at once across all connections, pondering included; more wait
+    /// in a queue
+    pub max_concurrent_searches: usize,
+    /// Search budgets by difficulty name, replacing the built-in ones or adding new difficulties
+    pub budgets: BTreeMap<String, SearchBudget>,
+    /// Most connections served at once, with no limit if `None`. Connections beyond it get a
+    /// `serverFull` error and are closed.
+    pub max_sessions: Option<usize>,
+    /// How long a game is kept once no request has used it, in seconds: a closed connection's
+    /// games can be resumed until then
+    pub session_timeout_secs: u64,
+    /// Most games kept at once, open or waiting to be resumed. Starting more gets a
+    /// `tooManyGames` error.
+    pub max_games: usize,
+    /// Most games open on one connection, counting resumed ones
+    pub max_games_per_connection: usize,
+    pub log_level: LogLevel,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: "127.0.0.1".to_string(),
+            port: 8080,
+            worker_threads: None,
+            max_concurrent_searches: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            budgets: BTreeMap::new(),
+            max_sessions: None,
+            session_timeout_secs: 600,
+            max_games: 1000,
+            max_games_per_connection: 8,
+            log_level: LogLevel::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    Read {
+        path: String,
+        message: String,
+    },
+    Parse {
+        path: String,
+        message: String,
+    },
+    UnknownFlag(String),
+    MissingValue(String),
+    InvalidValue {
+        flag: String,
+        value: String,
+    },
+    /// Config JSON that doesn't parse, before it's tied to a file
+    InvalidJson(String),
+    /// A setting in a config file with a value its flag wouldn't take, e.g. a zero limit
+    InvalidSetting {
+        setting: String,
+        value: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read { path, message } => {
+                write!(f, "Couldn't read config file {}: {}", path, message)
+            }
+            ConfigError::Parse { path, message } => {
+                write!(f, "Invalid config file {}: {}", path, message)
+            }
+            ConfigError::UnknownFlag(flag) => write!(f, "Unknown option {}", flag),
+            ConfigError::MissingValue(flag) => write!(f, "{} needs a value", flag),
+            ConfigError::InvalidValue { flag, value } => {
+                write!(f, "Invalid value for {}: {}", flag, value)
+            }
+            ConfigError::InvalidJson(message) => write!(f, "Invalid config: {}", message),
+            ConfigError::InvalidSetting { setting, value } => {
+                write!(f, "Invalid value for {} in config: {}", setting, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl ServerConfig {
+    /// The address to listen on, `bind:port`.
+    pub fn addr(&self) -> String {
+        format!("{}:{}", self.bind, self.port)
+    }
+
+    /// Parse JSON config, checking its settings like the flags are checked.
+    pub fn from_json(json: &str) -> Result<Self, ConfigError> {
+        let config: Self =
+            serde_json::from_str(json).map_err(|e| ConfigError::InvalidJson(e.to_string()))?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Check the settings the flags refuse: limits and budgets of zero, and unnamed
+    /// difficulties.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |setting: &str, value: String| {
+            Err(ConfigError::InvalidSetting {
+                setting: setting.to_string(),
+                value,
+            })
+        };
+
+        if self.worker_threads == Some(0) {
+            return invalid("workerThreads", "0".to_string());
+        }
+        if self.max_concurrent_searches == 0 {
+            return invalid("maxConcurrentSearches", "0".to_string());
+        }
+        for (difficulty, budget) in &self.budgets {
+            let zero = matches!(
+                budget,
+                SearchBudget::Iterations(0) | SearchBudget::Millis(0)
+            );
+            if difficulty.is_empty() || zero {
+                return invalid(
+                    &format!("budgets.{}", difficulty),
+                    serde_json::to_string(budget).unwrap_or_default(),
+                );
+            }
+        }
+        if self.max_sessions == Some(0) {
+            return invalid("maxSessions", "0".to_string());
+        }
+        if self.max_games == 0 {
+            return invalid("maxGames", "0".to_string());
+        }
+        if self.max_games_per_connection == 0 {
+            return invalid("maxGamesPerConnection", "0".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Read a JSON config file. Settings it leaves out keep their defaults.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).map_err(|e| ConfigError::Read {
+            path: path.display().to_string(),
+            message: e.to_string(),
+        })?;
+
+        Self::from_json(&json).map_err(|e| match e {
+            ConfigError::InvalidJson(message) => ConfigError::Parse {
+                path: path.display().to_string(),
+                message,
+            },
+            e => e,
+        })
+    }
+
+    /// The settings from command-line arguments (without the program name): the defaults, or the
+    /// file given with `--config`, overridden by any other flags. Values may be given as
+    /// `--flag value` or `--flag=value`.
+    pub fn from_args<I, S>(args: I) -> Result<Self, ConfigError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let flags = split_flags(args)?;
+
+        let mut config = match flags.iter().rev().find(|(flag, _)| flag == "--config") {
+            Some((_, path)) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        for (flag, value) in &flags {
+            config.apply_flag(flag, value)?;
+        }
+
+        Ok(config)
+    }
+
+    fn apply_flag(&mut self, flag: &str, value: &str) -> Result<(), ConfigError> {
+        let invalid = || ConfigError::InvalidValue {
+            flag: flag.to_string(),
+            value: value.to_string(),
+        };
+
+        match flag {
+            "--config" => {}
+            "--bind" => self.bind = value.to_string(),
+            "--port" => self.port = value.parse().map_err(|_| invalid())?,
+            "--threads" => {
+                self.worker_threads = Some(parse_positive(value).ok_or_else(invalid)?);
+            }
+            "--search-threads" => {
+                self.max_concurrent_searches = parse_positive(value).ok_or_else(invalid)?;
+            }
+            "--budget" => {
+                let (difficulty, budget) = value.split_once('=').ok_or_else(invalid)?;
+                let budget = parse_budget(budget).ok_or_else(invalid)?;
+                if difficulty.is_empty() {
+                    return Err(invalid());
+                }
+                self.budgets.insert(difficulty.to_string(), budget);
+            }
+            "--max-sessions" => {
+                self.max_sessions = Some(parse_positive(value).ok_or_else(invalid)?);
+            }
+            "--session-timeout" => {
+                self.session_timeout_secs = value.parse().map_err(|_| invalid())?;
+            }
+            "--max-games" => self.max_games = parse_positive(value).ok_or_else(invalid)?,
+            "--max-games-per-connection" => {
+                self.max_games_per_connection = parse_positive(value).ok_or_else(invalid)?;
+            }
+            "--log-level" => self.log_level = LogLevel::from_name(value).ok_or_else(invalid)?,
+            _ => return Err(ConfigError::UnknownFlag(flag.to_string())),
+        }
+
+        Ok(())
+    }
+}
+
+// Pair each flag with its value
+fn split_flags<I, S>(args: I) -> Result<Vec<(String, String)>, ConfigError>
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    let mut args = args.into_iter().map(Into::into);
+    let mut flags = Vec::new();
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            return Err(ConfigError::UnknownFlag(arg));
+        }
+
+        let (flag, value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), value.to_string()),
+            None => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| ConfigError::MissingValue(arg.clone()))?;
+                (arg, value)
+            }
+        };
+        flags.push((flag, value));
+    }
+
+    Ok(flags)
+}
+
+fn parse_positive(value: &str) -> Option<usize> {
+    value.parse().ok().filter(|&n| n > 0)
+}
+
+// `1500ms` or `200000`
+fn parse_budget(value: &str) -> Option<SearchBudget> {
+    match value.strip_suffix("ms") {
+        Some(millis) => millis
+            .parse()
+            .ok()
+            .filter(|&n| n > 0)
+            .map(SearchBudget::Millis),
+        None => parse_positive(value).map(SearchBudget::Iterations),
+    }
+}
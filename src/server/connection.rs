@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+use crate::server::games::GameStore;
+use crate::server::log;
+use crate::server::protocol::{ErrorCode, ForGame, Request, Response};
+use crate::server::search::{QueuedJob, SearchJob, SearchOutcome, SearchPool, SearchUpdate};
+use crate::server::session::{Reply, Session};
+
+/// The games open on one websocket, and the searches running for them. Replies come back tagged
+/// with their game's ID.
+pub(crate) struct Connection {
+    games: GameStore,
+    pool: SearchPool,
+    sessions: HashMap<String, Session>,
+    // The game for requests without an ID: one started by the first of them, unless a game was
+    // resumed first
+    default_game: Option<String>,
+    // Searches handed out for each game that haven't come back yet, abandoned ones included
+    pending: HashMap<String, usize>,
+    // Searches handed out that may still be waiting for their turn in the pool
+    queued: Vec<(String, QueuedJob)>,
+    updates: UnboundedSender<(String, SearchUpdate)>,
+    outcomes: UnboundedSender<(String, SearchOutcome)>,
+}
+
+impl Connection {
+    pub(crate) fn new(
+        games: GameStore,
+        pool: SearchPool,
+        updates: UnboundedSender<(String, SearchUpdate)>,
+        outcomes: UnboundedSender<(String, SearchOutcome)>,
+    ) -> Self {
+        Self {
+            games,
+            pool,
+            sessions: HashMap::new(),
+            default_game: None,
+            pending: HashMap::new(),
+            queued: Vec::new(),
+            updates,
+            outcomes,
+        }
+    }
+
+    pub(crate) fn handle_text(&mut self, text: &str) -> Vec<ForGame<Response>> {
+        self.touch_games();
+
+        let ForGame { game, message } = match serde_json::from_str::<ForGame<Request>>(text) {
+            Ok(request) => request,
+            Err(e) => {
+                return vec![ForGame::untagged(Response::error(
+                    ErrorCode::InvalidRequest,
+                    format!("Invalid request: {}", e),
+                    None,
+                ))];
+            }
+        };
+
+        match message {
+            Request::NewGame => match self.start_game(&message) {
+                Ok(id) => vec![ForGame::new(&id, ack(&message))],
+                Err(error) => vec![ForGame::untagged(error)],
+            },
+            Request::Resume => {
+                let Some(id) = game else {
+                    return vec![ForGame::untagged(Response::error(
+                        ErrorCode::InvalidRequest,
+                        "Send the ID of the game to resume as `game`",
+                        Some(&message),
+                    ))];
+                };
+                if !self.sessions.contains_key(&id) {
+                    if self.is_full() {
+                        return vec![ForGame::new(&id, too_many_games(&message))];
+                    }
+                    match self.games.resume(&id) {
+                        Ok(session) => {
+                            log::info!("Resumed game {}", id);
+                            self.sessions.insert(id.clone(), session);
+                            // a client that lost its connection carries on without IDs
+                            if self.default_game.is_none() {
+                                self.default_game = Some(id.clone());
+                            }
+                        }
+                        Err(code) => {
+                            let reason = match code {
+                                ErrorCode::GameInUse => "is open on another connection",
+                                _ => "doesn't exist or has expired",
+                            };
+                            let error = Response::error(
+                                code,
+                                format!("Game {} {}", id, reason),
+                                Some(&message),
+                            );
+                            return vec![ForGame::new(&id, error)];
+                        }
+                    }
+                }
+                self.handle(&id, Request::GetState)
+            }
+            Request::CloseGame => {
+                let id = match game.or_else(|| self.default_game.clone()) {
+                    Some(id) => id,
+                    None => return vec![ForGame::untagged(ack(&message))],
+                };
+                let Some(mut session) = self.sessions.remove(&id) else {
+                    return vec![unknown_game(&id, &message)];
+                };
+                session.cancel_search();
+                self.games.remove(&id);
+                self.default_game.take_if(|default| *default == id);
+                log::info!("Closed game {}", id);
+                vec![ForGame::new(&id, ack(&message))]
+            }
+            request => {
+                let id = match game {
+                    Some(id) if self.sessions.contains_key(&id) => id,
+                    Some(id) => return vec![unknown_game(&id, &request)],
+                    None => match self.default_game(&request) {
+                        Ok(id) => id,
+                        Err(error) => return vec![ForGame::untagged(error)],
+                    },
+                };
+                self.handle(&id, request)
+            }
+        }
+    }
+
+    // The client is still here, so its games are still in use. Any that expired while it was
+    // away too long are closed.
+    fn touch_games(&mut self) {
+        let games = &self.games;
+        let expired: Vec<String> = self
+            .sessions
+            .keys()
+            .filter(|id| !games.touch(id))
+            .cloned()
+            .collect();
+
+        for id in expired {
+            if let Some(mut session) = self.sessions.remove(&id) {
+                session.cancel_search();
+            }
+            self.default_game.take_if(|default| *default == id);
+            log::info!("Game {} expired", id);
+        }
+    }
+
+    fn default_game(&mut self, request: &Request) -> Result<String, Response> {
+        if let Some(id) = &self.default_game {
+            return Ok(id.clone());
+        }
+
+        let id = self.start_game(request)?;
+        self.default_game = Some(id.clone());
+        Ok(id)
+    }
+
+    fn is_full(&self) -> bool {
+        self.sessions.len() >= self.games.limits().max_per_connection
+    }
+
+    // Start a game on this connection, if both it and the store have room
+    fn start_game(&mut self, request: &Request) -> Result<String, Response> {
+        if self.is_full() {
+            return Err(too_many_games(request));
+        }
+        let Ok((id, session)) = self.games.create() else {
+            log::warn!("Refused a game: the server is keeping as many as it can");
+            return Err(Response::error(
+                ErrorCode::TooManyGames,
+                "The server is keeping as many games as it can",
+                Some(request),
+            ));
+        };
+
+        log::info!("Started game {}", id);
+        self.sessions.insert(id.clone(), session);
+        Ok(id)
+    }
+
+    // Pass a request to an open game
+    fn handle(&mut self, id: &str, request: Request) -> Vec<ForGame<Response>> {
+        let session = self.sessions.get_mut(id).expect("The game is open");
+        match session.handle(request) {
+            Reply::Responses(responses) => tag(id, responses),
+            Reply::Search(job) => {
+                self.spawn_search(id, *job);
+                Vec::new()
+            }
+        }
+    }
+
+    fn spawn_search(&mut self, id: &str, job: SearchJob) {
+        *self.pending.entry(id.to_string()).or_default() += 1;
+        let job = QueuedJob::new(job);
+        self.queued.retain(|(_, job)| job.is_waiting());
+        self.queued.push((id.to_string(), job.clone()));
+
+        let pool = self.pool.clone();
+        let updates = self.updates.clone();
+        let outcomes = self.outcomes.clone();
+        let id = id.to_string();
+        tokio::spawn(async move {
+            let update_id = id.clone();
+            let outcome = pool
+                .run_queued(job, move |update| {
+                    // the connection may have closed already
+                    let _ = updates.send((update_id.clone(), update));
+                })
+                .await;
+            // a reclaimed job's outcome was handled when it was taken back
+            if let Some(outcome) = outcome {
+                let _ = outcomes.send((id, outcome));
+            }
+        });
+    }
+
+    /// The message to send for a partial result from a search, unless it's been abandoned.
+    pub(crate) fn accept_update(
+        &self,
+        id: &str,
+        update: SearchUpdate,
+    ) -> Option<ForGame<Response>> {
+        let response = self.sessions.get(id)?.accept_update(update)?;
+        Some(ForGame::new(id, response))
+    }
+
+    /// The replies for a search that's come back, starting the game's next search if it has
+    /// one.
+    pub(crate) fn finish_search(
+        &mut self,
+        id: &str,
+        outcome: SearchOutcome,
+    ) -> Vec<ForGame<Response>> {
+        if let Some(pending) = self.pending.get_mut(id) {
+            *pending -= 1;
+        }
+        // The game may have been closed since
+        let Some(session) = self.sessions.get_mut(id) else {
+            return Vec::new();
+        };
+
+        let responses = session.finish_search(outcome);
+        if let Some(job) = session.next_search() {
+            self.spawn_search(id, *job);
+        }
+        tag(id, responses)
+    }
+
+    /// Leave the games for a later connection to resume, once their searches have stopped and
+    /// handed back the engine's trees. Searches still waiting for their turn are taken back
+    /// rather than waited for.
+    pub(crate) async fn close(mut self, outcomes: &mut UnboundedReceiver<(String, SearchOutcome)>) {
+        for session in self.sessions.values_mut() {
+            session.suspend();
+        }
+        for (id, job) in std::mem::take(&mut self.queued) {
+            if let Some(job) = job.reclaim() {
+                self.finish_search(&id, job.unsearched());
+            }
+        }
+        while self.pending.values().any(|&pending| pending > 0) {
+            let Some((id, outcome)) = outcomes.recv().await else {
+                break;
+            };
+            self.finish_search(&id, outcome);
+        }
+
+        for (id, session) in std::mem::take(&mut self.sessions) {
+            log::debug!("Keeping game {} for {:?}", id, self.games.timeout());
+            self.games.leave(&id, session);
+        }
+    }
+}
+
+// Hand back any games `close` didn't get to, e.g. if the connection's task panicked or was
+// aborted. Their searches can't be waited for here, so the engine's trees are lost.
+impl Drop for Connection {
+    fn drop(&mut self) {
+        for (id, mut session) in self.sessions.drain() {
+            session.cancel_search();
+            log::debug!("Keeping game {} for {:?}", id, self.games.timeout());
+            self.games.leave(&id, session);
+        }
+    }
+}
+
+fn ack(request: &Request) -> Response {
+    Response::Ack {
+        request: request.name().to_string(),
+    }
+}
+
+fn too_many_games(request: &Request) -> Response {
+    Response::error(
+        ErrorCode::TooManyGames,
+        "This connection has as many games open as it may; close one first",
+        Some(request),
+    )
+}
+
+fn unknown_game(id: &str, request: &Request) -> ForGame<Response> {
+    let error = Response::error(
+        ErrorCode::UnknownGame,
+        format!("Game {} isn't open on this connection", id),
+        Some(request),
+    );
+    ForGame::new(id, error)
+}
+
+fn tag(id: &str, responses: Vec<Response>) -> Vec<ForGame<Response>> {
+    responses
+        .into_iter()
+        .map(|response| ForGame::new(id, response))
+        .collect()
+}
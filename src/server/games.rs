@@ -0,0 +1,149 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::mcts::SearchBudget;
+use crate::server::protocol::ErrorCode;
+use crate::server::session::Session;
+
+/// The games on the server by ID, shared by all connections. A connection holds the sessions of
+/// the games it's playing, and hands them back when it closes so a client can `resume` them from
+/// another connection. Games nobody has used for longer than the timeout, whether left like that
+/// or still open, are dropped by `expire`.
+#[derive(Clone)]
+pub struct GameStore {
+    games: Arc<Mutex<HashMap<String, StoredGame>>>,
+    budgets: Arc<BTreeMap<String, SearchBudget>>,
+    timeout: Duration,
+    limits: GameLimits,
+}
+
+/// How many games may be kept at once. Each game has its own engine and search tree, so these
+/// bound the memory games can take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameLimits {
+    /// Games in the store, open or waiting to be resumed
+    pub max_games: usize,
+    /// Games open on one connection
+    pub max_per_connection: usize,
+}
+
+struct StoredGame {
+    // Waiting for a client since its connection closed, or `None` while it's open on a
+    // connection, which has the session
+    left: Option<Box<Session>>,
+    // When a connection last used the game, or left it
+    last_active: Instant,
+}
+
+impl StoredGame {
+    fn open() -> Self {
+        Self {
+            left: None,
+            last_active: Instant::now(),
+        }
+    }
+}
+
+impl GameStore {
+    /// A store whose games search with `budgets` (see `Session::with_budgets`) and are kept for
+    /// `timeout` after they were last used.
+    pub fn new(
+        budgets: BTreeMap<String, SearchBudget>,
+        timeout: Duration,
+        limits: GameLimits,
+    ) -> Self {
+        Self {
+            games: Arc::new(Mutex::new(HashMap::new())),
+            budgets: Arc::new(budgets),
+            timeout,
+            limits,
+        }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn limits(&self) -> GameLimits {
+        self.limits
+    }
+
+    // A panic while the lock was held can't have left a game half moved
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, StoredGame>> {
+        self.games.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Start a game under a new ID, open on the caller's connection, unless the store already
+    /// has as many games as it may.
+    pub fn create(&self) -> Result<(String, Session), ErrorCode> {
+        let mut games = self.lock();
+        if games.len() >= self.limits.max_games {
+            return Err(ErrorCode::TooManyGames);
+        }
+
+        let id = loop {
+            let id = format!("{:016x}", rand::random::<u64>());
+            if !games.contains_key(&id) {
+                break id;
+            }
+        };
+        games.insert(id.clone(), StoredGame::open());
+
+        Ok((id, Session::with_budgets((*self.budgets).clone())))
+    }
+
+    /// Take a game left by a closed connection, to carry on with it.
+    pub fn resume(&self, id: &str) -> Result<Session, ErrorCode> {
+        let mut games = self.lock();
+        let game = games.get_mut(id).ok_or(ErrorCode::UnknownGame)?;
+        let session = game.left.take().ok_or(ErrorCode::GameInUse)?;
+        game.last_active = Instant::now();
+
+        Ok(*session)
+    }
+
+    /// Note that a game open on the caller's connection is still in use, so it doesn't expire.
+    /// False if it has expired already.
+    pub fn touch(&self, id: &str) -> bool {
+        match self.lock().get_mut(id) {
+            Some(game) => {
+                game.last_active = Instant::now();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Hand back a game whose connection is closing, unless it has expired meanwhile. Its search
+    /// should have been suspended and finished first, so the engine's tree is kept with it.
+    pub fn leave(&self, id: &str, session: Session) {
+        if let Some(game) = self.lock().get_mut(id) {
+            game.left = Some(Box::new(session));
+            game.last_active = Instant::now();
+        }
+    }
+
+    /// Drop a game for good.
+    pub fn remove(&self, id: &str) {
+        self.lock().remove(id);
+    }
+
+    /// Drop the games nobody has used for the timeout as of `now`, returning how many there
+    /// were. Connections find out that their open games have gone from `touch`.
+    pub fn expire(&self, now: Instant) -> usize {
+        let mut games = self.lock();
+        let before = games.len();
+        games.retain(|_, game| now.saturating_duration_since(game.last_active) < self.timeout);
+        before - games.len()
+    }
+
+    /// How many games there are, open or waiting to be resumed.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -0,0 +1,185 @@
+//! The websocket server the web frontend plays against. Each game gets its own [`Session`],
+//! kept in a [`GameStore`] so a client can come back to it after reconnecting; the messages are
+//! described in [`protocol`]. Searches run on a [`SearchPool`], so the connection keeps handling
+//! messages while the engine thinks or ponders.
+
+mod config;
+mod connection;
+mod games;
+pub mod log;
+pub mod protocol;
+mod search;
+mod session;
+
+pub use config::{ConfigError, ServerConfig, USAGE};
+pub use games::{GameLimits, GameStore};
+pub use search::{SearchJob, SearchOutcome, SearchPool, SearchUpdate};
+pub use session::{Reply, Session};
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Semaphore;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{WebSocketStream, accept_async};
+
+use connection::Connection;
+use protocol::{ErrorCode, ForGame, Response};
+
+/// Serve one connection, playing games from `games`, until it closes. Its games are left in the
+/// store for a later connection to resume.
+pub async fn handle_websocket(
+    ws_stream: WebSocketStream<TcpStream>,
+    pool: SearchPool,
+    games: GameStore,
+) {
+    let (mut ws_sender, mut ws_receiver) = ws_stream.split();
+    let (updates_tx, mut updates_rx) = mpsc::unbounded_channel();
+    let (outcomes_tx, mut outcomes_rx) = mpsc::unbounded_channel();
+    let mut connection = Connection::new(games, pool, updates_tx, outcomes_tx);
+
+    'connection: loop {
+        // The connection holds both senders, so the channels stay open
+        let responses = tokio::select! {
+            msg = ws_receiver.next() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    log::debug!("Received: {}", text);
+                    connection.handle_text(&text)
+                }
+                Some(Ok(Message::Binary(_))) => vec![ForGame::untagged(Response::error(
+                    ErrorCode::InvalidRequest,
+                    "Binary messages aren't supported",
+                    None,
+                ))],
+                Some(Ok(Message::Close(_))) | None => {
+                    log::info!("Client disconnected");
+                    break;
+                }
+                Some(Ok(_)) => {
+                    // Pings and pongs are answered by tungstenite
+                    continue;
+                }
+                Some(Err(e)) => {
+                    log::warn!("WebSocket error: {}", e);
+                    break;
+                }
+            },
+            Some((game, update)) = updates_rx.recv() => {
+                match connection.accept_update(&game, update) {
+                    Some(response) => vec![response],
+                    None => continue,
+                }
+            }
+            Some((game, outcome)) = outcomes_rx.recv() => connection.finish_search(&game, outcome),
+        };
+
+        for response in responses {
+            if let Err(e) = ws_sender.send(Message::Text(response.to_json())).await {
+                log::warn!("Failed to send response: {}", e);
+                break 'connection;
+            }
+        }
+    }
+
+    connection.close(&mut outcomes_rx).await;
+}
+
+// Drop the games nobody came back for, every so often
+async fn expire_games(games: GameStore) {
+    let mut interval = tokio::time::interval((games.timeout() / 4).max(Duration::from_secs(1)));
+    loop {
+        interval.tick().await;
+        let expired = games.expire(Instant::now());
+        if expired > 0 {
+            log::info!(
+                "Dropped {} games unused for over {:?}",
+                expired,
+                games.timeout()
+            );
+        }
+    }
+}
+
+// Turn a connection away once the server is full
+async fn refuse_websocket(mut ws_stream: WebSocketStream<TcpStream>) {
+    let response = Response::error(
+        ErrorCode::ServerFull,
+        "The server is serving as many connections as it can",
+        None,
+    );
+    if ws_stream
+        .send(Message::Text(response.to_json()))
+        .await
+        .is_ok()
+    {
+        let _ = ws_stream.close(None).await;
+    }
+}
+
+/// Accept websocket connections on the configured address until the listener fails.
+pub async fn serve(config: ServerConfig) -> std::io::Result<()> {
+    let addr = config.addr();
+    let listener = TcpListener::bind(&addr).await?;
+    log::info!("WebSocket server listening on ws://{}", addr);
+
+    serve_on(listener, config).await
+}
+
+/// Accept websocket connections from an already bound listener until it fails. The listener's
+/// address is used rather than the configured one.
+pub async fn serve_on(listener: TcpListener, config: ServerConfig) -> std::io::Result<()> {
+    let pool = SearchPool::new(config.max_concurrent_searches);
+    let games = GameStore::new(
+        config.budgets.clone(),
+        Duration::from_secs(config.session_timeout_secs),
+        GameLimits {
+            max_games: config.max_games,
+            max_per_connection: config.max_games_per_connection,
+        },
+    );
+    let sessions = config.max_sessions.map(|n| Arc::new(Semaphore::new(n)));
+
+    let expiry = tokio::spawn(expire_games(games.clone()));
+    let result = accept_connections(listener, pool, games, sessions).await;
+    expiry.abort();
+    result
+}
+
+async fn accept_connections(
+    listener: TcpListener,
+    pool: SearchPool,
+    games: GameStore,
+    sessions: Option<Arc<Semaphore>>,
+) -> std::io::Result<()> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let pool = pool.clone();
+        let games = games.clone();
+        // Held until the connection closes
+        let permit = sessions.as_ref().map(|s| s.clone().try_acquire_owned());
+
+        tokio::spawn(async move {
+            let ws_stream = match accept_async(stream).await {
+                Ok(ws_stream) => ws_stream,
+                Err(e) => {
+                    log::warn!("Failed to accept WebSocket: {}", e);
+                    return;
+                }
+            };
+
+            match permit {
+                Some(Err(_)) => {
+                    log::warn!("Refused a connection: the server is full");
+                    refuse_websocket(ws_stream).await;
+                }
+                Some(Ok(_)) | None => {
+                    log::info!("Client connected");
+                    handle_websocket(ws_stream, pool, games).await;
+                }
+            }
+        });
+    }
+}
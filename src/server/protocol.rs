@@ -0,0 +1,334 @@
+//! Messages exchanged with the web frontend over the websocket, as JSON objects tagged by
+//! `type`.
+//!
+//! A client may open with a `hello` listing the protocol versions it speaks; the server answers
+//! with the version it picked, its name and its capabilities. Clients that skip the handshake get
+//! the current version. Every request gets at least one reply: `error` if it couldn't be handled.
+//!
+//! While the engine is searching, `findMove` is refused as `busy`. A `stop` ends the search
+//! early and its reply is the engine's move; a new `init` abandons the search without a move.
+//!
+//! A game started with `ponder` set keeps the engine searching while the client thinks. The
+//! `findMove` that ends it is answered the same way, using what was found in the meantime.
+//!
+//! A client that lost track of the game (e.g. after a reload or an undo) can resynchronize with
+//! `setPosition`, `undo` and `getState`, which all reply with the server's `state`. The first two
+//! abandon any search, like `init`.
+//!
+//! `analyze` and `evaluate` search a position without playing anything. They send partial
+//! `analysis` or `evaluation` messages as the search goes, and a last one with `final` set.
+//!
+//! While the engine searches for its move, it sends `info` messages every half second or so
+//! with its current choice, until the `move`.
+//!
+//! Games have IDs, sent as `game` alongside the other fields of every reply about a game.
+//! Requests with a `game` go to that game, and those without one to the connection's default
+//! game: the first game it resumed, unless a request without an ID came first and started one.
+//! `newGame` starts another game on the same connection, and `closeGame` ends one. The server
+//! keeps a connection's games for a while after it closes: a new connection can carry on with
+//! one by sending `resume` with its ID, which replies with its `state`. There's a limit on the
+//! games open on a connection and kept on the server, beyond which starting or resuming a game
+//! gets a `tooManyGames` error.
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::{GameResult, Player, Score, StartPosition};
+
+/// The protocol version the server speaks.
+pub const PROTOCOL_VERSION: u32 = 1;
+/// The oldest protocol version the server still accepts.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// The request types this server handles, announced in the `hello` reply.
+pub const CAPABILITIES: &[&str] = &[
+    "hello",
+    "init",
+    "findMove",
+    "stop",
+    "positionNotation",
+    "ponder",
+    "setPosition",
+    "undo",
+    "getState",
+    "analyze",
+    "evaluate",
+    "games",
+];
+
+pub fn engine_name() -> String {
+    format!("blok-rs {}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Pick the newest version both sides speak, from the versions the client listed.
+pub fn negotiate_version(client_versions: &[u32]) -> Option<u32> {
+    client_versions
+        .iter()
+        .copied()
+        .filter(|v| (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(v))
+        .max()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Request {
+    #[serde(rename_all = "camelCase")]
+    Hello {
+        protocol_versions: Vec<u32>,
+    },
+    Init {
+        #[serde(rename = "startPos")]
+        start_pos: String,
+        difficulty: String,
+        /// Position to start from, in `BoardState::to_notation` form
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        position: Option<String>,
+        /// Keep searching on the client's time
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        ponder: bool,
+        /// The side the engine plays. Without it, the engine plays whichever side is to move
+        /// when asked.
+        #[serde(
+            rename = "engineSide",
+            default,
+            skip_serializing_if = "Option::is_none"
+        )]
+        engine_side: Option<Player>,
+    },
+    /// Play the client's move, if any, then reply with the engine's, and with `gameOver` once
+    /// neither side can move.
+    FindMove {
+        #[serde(rename = "move", default, skip_serializing_if = "Option::is_none")]
+        mov: Option<u32>,
+    },
+    /// Play the best move found so far by the running search. Acked if nothing is running.
+    Stop,
+    /// Replace the game with the given moves played from the start (or from `position`), keeping
+    /// the settings from `init`.
+    SetPosition {
+        #[serde(rename = "startPos")]
+        start_pos: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        position: Option<String>,
+        moves: Vec<u32>,
+    },
+    /// Take back the last `count` moves, 1 if not given.
+    Undo {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        count: Option<usize>,
+    },
+    GetState,
+    /// The best moves in the current position, 3 if `top` isn't given. Searches for as long as
+    /// a move at `difficulty` would, the game's difficulty by default.
+    Analyze {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        top: Option<usize>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        difficulty: Option<String>,
+    },
+    /// The chance the player to move wins from `position`, or from the current position if it
+    /// isn't given.
+    Evaluate {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        position: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        difficulty: Option<String>,
+    },
+    /// Start another game on this connection, acked with its ID. It's played like the first,
+    /// starting with `init` or `setPosition`.
+    NewGame,
+    /// Carry on with the game given as `game`, left by a connection that closed.
+    Resume,
+    /// End the game for good, abandoning any search.
+    CloseGame,
+}
+
+impl Request {
+    /// The request's `type` tag.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Request::Hello { .. } => "hello",
+            Request::Init { .. } => "init",
+            Request::FindMove { .. } => "findMove",
+            Request::Stop => "stop",
+            Request::SetPosition { .. } => "setPosition",
+            Request::Undo { .. } => "undo",
+            Request::GetState => "getState",
+            Request::Analyze { .. } => "analyze",
+            Request::Evaluate { .. } => "evaluate",
+            Request::NewGame => "newGame",
+            Request::Resume => "resume",
+            Request::CloseGame => "closeGame",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorCode {
+    /// Not valid JSON, or not a known request
+    InvalidRequest,
+    /// None of the client's protocol versions are supported
+    UnsupportedVersion,
+    InvalidStartPosition,
+    InvalidPosition,
+    IllegalMove,
+    /// A `findMove` for the side the engine doesn't play, or with a move for the engine's side
+    WrongTurn,
+    /// The engine is already finding a move or analysing, and can't be asked for more until it's
+    /// done
+    Busy,
+    /// The server is serving as many connections as it's configured for, and closes this one
+    ServerFull,
+    /// No game has the ID, or it isn't open on this connection; it may have expired
+    UnknownGame,
+    /// The game is still open on another connection
+    GameInUse,
+    /// The connection has as many games open as it may, or the server is keeping as many as it
+    /// can; closing a game makes room
+    TooManyGames,
+    Internal,
+}
+
+/// One of the best moves found by `analyze`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyzedMove {
+    #[serde(rename = "move")]
+    pub mov: u32,
+    pub notation: String,
+    pub visits: usize,
+    /// The share of playouts through the move won by the player making it
+    pub win_rate: f64,
+    /// The move and the line the engine expects to follow it
+    pub pv: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Response {
+    #[serde(rename_all = "camelCase")]
+    Hello {
+        protocol_version: u32,
+        engine: String,
+        capabilities: Vec<String>,
+    },
+    /// The request was handled and has no other reply.
+    Ack {
+        request: String,
+    },
+    Move {
+        #[serde(rename = "move")]
+        mov: u32,
+        notation: String,
+    },
+    GameOver {
+        result: GameResult,
+        score: Score,
+    },
+    /// The game as the server has it.
+    #[serde(rename_all = "camelCase")]
+    State {
+        start_pos: StartPosition,
+        /// The position the moves were played from, if the game didn't start on an empty board
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        initial_position: Option<String>,
+        moves: Vec<u32>,
+        /// The current board, in `BoardState::to_notation` form
+        position: String,
+        #[serde(rename = "toMove")]
+        player: Player,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        engine_side: Option<Player>,
+        score: Score,
+        result: GameResult,
+    },
+    /// How the search for the engine's move is going.
+    #[serde(rename_all = "camelCase")]
+    Info {
+        iterations: usize,
+        elapsed_ms: u64,
+        /// The move the engine would play if the search ended now
+        #[serde(rename = "bestMove")]
+        best_move: u32,
+        notation: String,
+        visits: usize,
+        /// The share of playouts through `best_move` won by the engine
+        win_rate: f64,
+        pv: Vec<u32>,
+    },
+    /// The best moves found by `analyze` so far, most visited first.
+    Analysis {
+        #[serde(rename = "final")]
+        is_final: bool,
+        iterations: usize,
+        moves: Vec<AnalyzedMove>,
+    },
+    /// The chance the player to move wins, as far as `evaluate` has searched.
+    #[serde(rename_all = "camelCase")]
+    Evaluation {
+        #[serde(rename = "final")]
+        is_final: bool,
+        iterations: usize,
+        #[serde(rename = "toMove")]
+        player: Player,
+        win_probability: f64,
+    },
+    Error {
+        code: ErrorCode,
+        message: String,
+        /// The `type` of the request that failed, if it could be parsed
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        request: Option<String>,
+        /// The rejected move, for `illegalMove`
+        #[serde(rename = "move", default, skip_serializing_if = "Option::is_none")]
+        mov: Option<u32>,
+    },
+}
+
+impl Response {
+    pub fn error(code: ErrorCode, message: impl Into<String>, request: Option<&Request>) -> Self {
+        Response::Error {
+            code,
+            message: message.into(),
+            request: request.map(|r| r.name().to_string()),
+            mov: None,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Responses always serialize")
+    }
+}
+
+/// A message along with the ID of the game it's about, sent as `game` next to its other fields.
+/// Requests without an ID are for the connection's default game.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ForGame<T> {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game: Option<String>,
+    #[serde(flatten)]
+    pub message: T,
+}
+
+impl<T> ForGame<T> {
+    pub fn new(game: &str, message: T) -> Self {
+        Self {
+            game: Some(game.to_string()),
+            message,
+        }
+    }
+
+    /// A message that isn't about any one game.
+    pub fn untagged(message: T) -> Self {
+        Self {
+            game: None,
+            message,
+        }
+    }
+}
+
+impl ForGame<Response> {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Responses always serialize")
+    }
+}
@@ -0,0 +1,308 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use tokio::sync::Semaphore;
+
+use crate::board::BoardState;
+use crate::mcts::{MonteCarlo, SearchBudget, SearchProgress};
+use crate::movegen::Move;
+use crate::record::SearchStats;
+use crate::server::log;
+use crate::server::protocol::{AnalyzedMove, Response};
+
+/// What a search is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SearchKind {
+    /// The engine's move in the game
+    Move,
+    /// Searching ahead on the client's time
+    Ponder,
+    /// The `top` best moves, without playing any
+    Analyze { top: usize },
+    /// The win probability of the player to move
+    Evaluate,
+}
+
+/// A search handed out by a `Session` to be run away from the async runtime: the engine's move,
+/// pondering on the client's time, or an analysis. It owns a `MonteCarlo` (the session's, unless
+/// it's an analysis) until it's finished, and ends early once the session sets its stop flag.
+pub struct SearchJob {
+    pub(crate) id: u64,
+    pub(crate) kind: SearchKind,
+    pub(crate) board: BoardState,
+    pub(crate) budget: SearchBudget,
+    pub(crate) eval: MonteCarlo,
+    pub(crate) stop: Arc<AtomicBool>,
+    /// The moves from the root of `eval`'s tree to `board`, to keep the search below them. `None`
+    /// starts a new tree.
+    pub(crate) advance: Option<Vec<u32>>,
+}
+
+pub struct SearchOutcome {
+    pub(crate) id: u64,
+    pub(crate) eval: MonteCarlo,
+    pub(crate) best_move: Result<u32, String>,
+    pub(crate) stats: SearchStats,
+    pub(crate) think_time_ms: u64,
+    /// The final `analysis` or `evaluation`, for analysis searches
+    pub(crate) report: Option<Response>,
+}
+
+/// A partial result or progress report sent while a search is still running. Pass it to
+/// `Session::accept_update` to find out whether it should still go to the client.
+#[derive(Debug, Clone)]
+pub struct SearchUpdate {
+    pub(crate) id: u64,
+    pub(crate) response: Response,
+}
+
+impl SearchOutcome {
+    /// The move the search settled on, if it found one. For a ponder search, the reply it
+    /// expects from the client.
+    pub fn best_move(&self) -> Option<u32> {
+        self.best_move.as_ref().ok().copied()
+    }
+
+    // The engine's tree is lost along with the search
+    fn failed(id: u64, message: String) -> Self {
+        Self {
+            id,
+            eval: MonteCarlo::new(),
+            best_move: Err(message),
+            stats: SearchStats { plays: 0, wins: 0 },
+            think_time_ms: 0,
+            report: None,
+        }
+    }
+}
+
+// The `analysis` or `evaluation` message for the search so far
+fn report(
+    kind: SearchKind,
+    eval: &MonteCarlo,
+    board: &BoardState,
+    is_final: bool,
+) -> Option<Response> {
+    let iterations = eval.nodes.first().map_or(0, |root| root.n_plays);
+
+    match kind {
+        SearchKind::Move | SearchKind::Ponder => None,
+        SearchKind::Analyze { top } => Some(Response::Analysis {
+            is_final,
+            iterations,
+            moves: eval
+                .root_moves()
+                .iter()
+                .take(top)
+                .map(|m| AnalyzedMove {
+                    mov: m.play,
+                    notation: Move::to_notation(m.play),
+                    visits: m.visits,
+                    win_rate: m.win_rate(),
+                    pv: eval.principal_variation(m.play),
+                })
+                .collect(),
+        }),
+        SearchKind::Evaluate => Some(Response::Evaluation {
+            is_final,
+            iterations,
+            player: board.player,
+            win_probability: eval.win_rate().unwrap_or(0.5),
+        }),
+    }
+}
+
+fn info(progress: &SearchProgress) -> Response {
+    Response::Info {
+        iterations: progress.iterations,
+        elapsed_ms: progress.elapsed.as_millis() as u64,
+        best_move: progress.best.play,
+        notation: Move::to_notation(progress.best.play),
+        visits: progress.best.visits,
+        win_rate: progress.best.win_rate(),
+        pv: progress.pv.clone(),
+    }
+}
+
+impl SearchJob {
+    /// Run the search on the current thread, dropping any partial results.
+    pub fn run(self) -> SearchOutcome {
+        self.run_with_updates(|_| {})
+    }
+
+    /// Run the search on the current thread. Searches for a move report their progress to
+    /// `on_update` as `info` messages. Analysis searches report partial results instead, after an
+    /// eighth, a quarter and half of their iterations.
+    pub fn run_with_updates(self, mut on_update: impl FnMut(SearchUpdate)) -> SearchOutcome {
+        let SearchJob {
+            id,
+            kind,
+            board,
+            budget,
+            mut eval,
+            stop,
+            advance,
+        } = self;
+
+        let search_start = Instant::now();
+        advance_tree(&mut eval, advance);
+
+        // in eighths of the budget
+        let stages: &[usize] = match kind {
+            SearchKind::Move | SearchKind::Ponder => &[8],
+            SearchKind::Analyze { .. } | SearchKind::Evaluate => &[1, 1, 2, 4],
+        };
+
+        eval.stop = Some(stop.clone());
+        for (i, &stage) in stages.iter().enumerate() {
+            let stage_budget = budget.fraction(stage, 8);
+            if kind == SearchKind::Move {
+                eval.run_budget_with_progress(&board, stage_budget, &mut |progress| {
+                    on_update(SearchUpdate {
+                        id,
+                        response: info(progress),
+                    })
+                });
+            } else {
+                eval.run_budget(&board, stage_budget);
+            }
+            if i + 1 == stages.len() || stop.load(Ordering::Relaxed) {
+                break;
+            }
+            if let Some(response) = report(kind, &eval, &board, false) {
+                on_update(SearchUpdate { id, response });
+            }
+        }
+        eval.stop = None;
+
+        let best_move = eval.best_play().map_err(|e| e.to_string());
+        if kind == SearchKind::Move
+            && !stop.load(Ordering::Relaxed)
+            && !eval.is_root_fully_expanded()
+        {
+            log::warn!("Search {} ran out of budget before trying every move", id);
+        }
+        let (wins, plays) = eval.get_stats();
+        let report = report(kind, &eval, &board, true);
+
+        SearchOutcome {
+            id,
+            eval,
+            best_move,
+            stats: SearchStats { plays, wins },
+            think_time_ms: search_start.elapsed().as_millis() as u64,
+            report,
+        }
+    }
+
+    /// The outcome of a search taken back before it started: the tree is moved on to the job's
+    /// position, but not searched.
+    pub(crate) fn unsearched(self) -> SearchOutcome {
+        let SearchJob {
+            id,
+            mut eval,
+            advance,
+            ..
+        } = self;
+
+        advance_tree(&mut eval, advance);
+        let best_move = eval.best_play().map_err(|e| e.to_string());
+        let (wins, plays) = eval
+            .nodes
+            .first()
+            .map_or((0, 0), |root| (root.n_wins, root.n_plays));
+
+        SearchOutcome {
+            id,
+            eval,
+            best_move,
+            stats: SearchStats { plays, wins },
+            think_time_ms: 0,
+            report: None,
+        }
+    }
+}
+
+// Move the tree down to the search's position, or start a new one
+fn advance_tree(eval: &mut MonteCarlo, advance: Option<Vec<u32>>) {
+    match advance {
+        Some(moves) => {
+            // reroot clears the tree if it doesn't have a move
+            for m in moves {
+                if !eval.reroot(m) {
+                    break;
+                }
+            }
+        }
+        None => eval.clear(),
+    }
+}
+
+/// A search waiting for its turn in a `SearchPool`. Until the pool starts it, it can be taken
+/// back with `reclaim`.
+#[derive(Clone)]
+pub(crate) struct QueuedJob(Arc<Mutex<Option<SearchJob>>>);
+
+impl QueuedJob {
+    pub(crate) fn new(job: SearchJob) -> Self {
+        Self(Arc::new(Mutex::new(Some(job))))
+    }
+
+    /// The job, if the pool hasn't started it yet. The pool then skips it.
+    pub(crate) fn reclaim(&self) -> Option<SearchJob> {
+        self.0.lock().unwrap().take()
+    }
+
+    pub(crate) fn is_waiting(&self) -> bool {
+        self.0.lock().unwrap().is_some()
+    }
+}
+
+/// Runs searches on tokio's blocking thread pool, at most `max_concurrent` at a time. Searches
+/// beyond that wait their turn, first come first served.
+#[derive(Clone)]
+pub struct SearchPool {
+    permits: Arc<Semaphore>,
+}
+
+impl SearchPool {
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(max_concurrent.max(1))),
+        }
+    }
+
+    /// Run a search once there's room, passing its partial results to `on_update`. A search
+    /// that panics gives an outcome without a move rather than taking the connection down with
+    /// it.
+    pub async fn run(
+        &self,
+        job: SearchJob,
+        on_update: impl FnMut(SearchUpdate) + Send + 'static,
+    ) -> SearchOutcome {
+        self.run_queued(QueuedJob::new(job), on_update)
+            .await
+            .expect("Only the caller could reclaim the job")
+    }
+
+    /// Like `run`, for a job that may be reclaimed while it waits. Gives `None` if it was.
+    pub(crate) async fn run_queued(
+        &self,
+        job: QueuedJob,
+        on_update: impl FnMut(SearchUpdate) + Send + 'static,
+    ) -> Option<SearchOutcome> {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .expect("The search semaphore is never closed");
+        let job = job.reclaim()?;
+
+        let id = job.id;
+        let outcome = tokio::task::spawn_blocking(move || job.run_with_updates(on_update))
+            .await
+            .unwrap_or_else(|e| SearchOutcome::failed(id, format!("The search failed: {}", e)));
+        Some(outcome)
+    }
+}
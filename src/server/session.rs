@@ -0,0 +1,638 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::board::{BoardState, GameResult, Player, StartPosition};
+use crate::game::Game;
+use crate::mcts::{MonteCarlo, SearchBudget};
+use crate::movegen::Move;
+use crate::server::log;
+use crate::server::protocol::{
+    CAPABILITIES, ErrorCode, PROTOCOL_VERSION, Request, Response, engine_name, negotiate_version,
+};
+use crate::server::search::{SearchJob, SearchKind, SearchOutcome, SearchUpdate};
+
+/// One game on the server and the engine playing it.
+pub struct Session {
+    game: Game,
+    // `None` while a search has it
+    eval: Option<MonteCarlo>,
+    // The ply of `game` at the root of `eval`'s tree, if the tree belongs to this game
+    tree_ply: Option<usize>,
+    difficulty: String,
+    // Search budgets by difficulty, overriding the built-in ones
+    budgets: BTreeMap<String, SearchBudget>,
+    ponder: bool,
+    // The side the engine plays, or `None` to play whichever side is to move when asked
+    engine_side: Option<Player>,
+    protocol_version: u32,
+    search: Option<ActiveSearch>,
+    // A `findMove` that came in while pondering, waiting for the engine back. Holds the stop
+    // flag its search will get.
+    queued_move: Option<Arc<AtomicBool>>,
+    // Whether to ponder once nothing else is running
+    ponder_next: bool,
+    // A search stopped by `suspend` that has the engine's tree, which is kept when it comes back
+    suspended_search: Option<u64>,
+    next_search_id: u64,
+}
+
+// A search handed out that hasn't been finished or abandoned yet
+struct ActiveSearch {
+    id: u64,
+    stop: Arc<AtomicBool>,
+    kind: SearchKind,
+}
+
+/// What to do about a request: reply straight away, or run a search and pass its outcome to
+/// `Session::finish_search` for the replies.
+pub enum Reply {
+    Responses(Vec<Response>),
+    Search(Box<SearchJob>),
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            game: Game::new(StartPosition::Corner),
+            eval: Some(Self::new_eval()),
+            tree_ply: None,
+            difficulty: "hard".to_string(),
+            budgets: BTreeMap::new(),
+            ponder: false,
+            engine_side: None,
+            protocol_version: PROTOCOL_VERSION,
+            search: None,
+            queued_move: None,
+            ponder_next: false,
+            suspended_search: None,
+            next_search_id: 0,
+        }
+    }
+
+    /// A session searching for the given budgets, by difficulty, rather than the built-in ones.
+    /// Difficulties without one keep the built-in budget.
+    pub fn with_budgets(budgets: BTreeMap<String, SearchBudget>) -> Self {
+        Self {
+            budgets,
+            ..Self::new()
+        }
+    }
+
+    fn budget_for(&self, difficulty: &str) -> SearchBudget {
+        self.budgets
+            .get(difficulty)
+            .copied()
+            .unwrap_or_else(|| SearchBudget::for_difficulty(difficulty))
+    }
+
+    fn new_eval() -> MonteCarlo {
+        let mut eval = MonteCarlo::new();
+        eval.prune_root_symmetry = true;
+        eval
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn protocol_version(&self) -> u32 {
+        self.protocol_version
+    }
+
+    /// Whether the engine is finding a move or analysing, and hasn't replied yet.
+    pub fn is_searching(&self) -> bool {
+        self.search
+            .as_ref()
+            .is_some_and(|search| search.kind != SearchKind::Ponder)
+            || self.queued_move.is_some()
+    }
+
+    /// Whether a ponder search from `next_search` is running.
+    pub fn is_pondering(&self) -> bool {
+        self.search
+            .as_ref()
+            .is_some_and(|search| search.kind == SearchKind::Ponder)
+    }
+
+    /// The message to send for a partial result from a search, unless the search has been
+    /// abandoned since.
+    pub fn accept_update(&self, update: SearchUpdate) -> Option<Response> {
+        self.search
+            .as_ref()
+            .is_some_and(|search| search.id == update.id)
+            .then_some(update.response)
+    }
+
+    /// Abandon the running search and any queued one: they're told to stop, and their outcomes
+    /// will be ignored.
+    pub fn cancel_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.stop.store(true, Ordering::Relaxed);
+        }
+        self.queued_move = None;
+        self.ponder_next = false;
+        self.suspended_search = None;
+        self.tree_ply = None;
+    }
+
+    /// Stop the engine because the client has gone, keeping its tree for when they're back.
+    /// Like `cancel_search`, nothing is sent for the search, but its outcome should still be
+    /// passed to `finish_search` to get the tree back.
+    pub fn suspend(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.stop.store(true, Ordering::Relaxed);
+            if let SearchKind::Move | SearchKind::Ponder = search.kind {
+                self.suspended_search = Some(search.id);
+            }
+        }
+        self.queued_move = None;
+        self.ponder_next = false;
+    }
+
+    /// A search the session wants to start now that nothing is running: a move asked for while
+    /// pondering, or pondering after the engine's move. Call it once a search's outcome has
+    /// been passed to `finish_search`.
+    pub fn next_search(&mut self) -> Option<Box<SearchJob>> {
+        if self.search.is_some() {
+            return None;
+        }
+
+        if let Some(stop) = self.queued_move.take() {
+            return Some(self.start_search(stop, SearchKind::Move));
+        }
+
+        if std::mem::take(&mut self.ponder_next) && !self.game.board().is_game_over() {
+            return Some(self.start_search(Arc::new(AtomicBool::new(false)), SearchKind::Ponder));
+        }
+
+        None
+    }
+
+    fn new_search_id(&mut self) -> u64 {
+        let id = self.next_search_id;
+        self.next_search_id += 1;
+        id
+    }
+
+    // A search of the game with the engine's tree
+    fn start_search(&mut self, stop: Arc<AtomicBool>, kind: SearchKind) -> Box<SearchJob> {
+        let id = self.new_search_id();
+        self.search = Some(ActiveSearch {
+            id,
+            stop: stop.clone(),
+            kind,
+        });
+
+        // An abandoned search may still have the engine, in which case it gets a new one
+        let ply = self.game.ply();
+        let (eval, advance) = match self.eval.take() {
+            Some(eval) => {
+                let advance = self
+                    .tree_ply
+                    .filter(|&tree_ply| tree_ply <= ply)
+                    .map(|tree_ply| self.game.moves()[tree_ply..].to_vec());
+                (eval, advance)
+            }
+            None => (Self::new_eval(), None),
+        };
+        self.tree_ply = Some(ply);
+
+        Box::new(SearchJob {
+            id,
+            kind,
+            board: self.game.board().clone(),
+            budget: self.budget_for(&self.difficulty),
+            eval,
+            stop,
+            advance,
+        })
+    }
+
+    // An analysis with a tree of its own, interrupting any pondering
+    fn start_analysis(
+        &mut self,
+        kind: SearchKind,
+        board: BoardState,
+        difficulty: Option<&String>,
+    ) -> Box<SearchJob> {
+        self.cancel_search();
+        let id = self.new_search_id();
+        let stop = Arc::new(AtomicBool::new(false));
+        self.search = Some(ActiveSearch {
+            id,
+            stop: stop.clone(),
+            kind,
+        });
+
+        Box::new(SearchJob {
+            id,
+            kind,
+            board,
+            budget: self.budget_for(difficulty.unwrap_or(&self.difficulty)),
+            eval: Self::new_eval(),
+            stop,
+            advance: None,
+        })
+    }
+
+    /// Handle a text message from the client, replying with an error if it isn't a valid
+    /// request.
+    pub fn handle_text(&mut self, text: &str) -> Reply {
+        match serde_json::from_str::<Request>(text) {
+            Ok(request) => self.handle(request),
+            Err(e) => Reply::Responses(vec![Response::error(
+                ErrorCode::InvalidRequest,
+                format!("Invalid request: {}", e),
+                None,
+            )]),
+        }
+    }
+
+    /// Handle a request, running any search on the current thread.
+    pub fn handle_blocking(&mut self, request: Request) -> Vec<Response> {
+        match self.handle(request) {
+            Reply::Responses(responses) => responses,
+            Reply::Search(job) => self.finish_search(job.run()),
+        }
+    }
+
+    pub fn handle(&mut self, request: Request) -> Reply {
+        if self.is_searching()
+            && matches!(
+                request,
+                Request::FindMove { .. } | Request::Analyze { .. } | Request::Evaluate { .. }
+            )
+        {
+            return Reply::Responses(vec![Response::error(
+                ErrorCode::Busy,
+                "The engine is still searching",
+                Some(&request),
+            )]);
+        }
+
+        match self.handle_request(&request) {
+            Ok(reply) => reply,
+            Err(response) => Reply::Responses(vec![response]),
+        }
+    }
+
+    fn handle_request(&mut self, request: &Request) -> Result<Reply, Response> {
+        match request {
+            Request::Hello { protocol_versions } => {
+                let version = negotiate_version(protocol_versions).ok_or_else(|| {
+                    Response::error(
+                        ErrorCode::UnsupportedVersion,
+                        format!(
+                            "No supported protocol version in {:?}, the server speaks {}",
+                            protocol_versions, PROTOCOL_VERSION
+                        ),
+                        Some(request),
+                    )
+                })?;
+                self.protocol_version = version;
+
+                Ok(Reply::Responses(vec![Response::Hello {
+                    protocol_version: version,
+                    engine: engine_name(),
+                    capabilities: CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+                }]))
+            }
+            Request::Init {
+                start_pos,
+                difficulty,
+                position,
+                ponder,
+                engine_side,
+            } => {
+                let game = new_game(request, start_pos, position.as_deref())?;
+
+                self.difficulty = difficulty.clone();
+                self.ponder = *ponder;
+                self.engine_side = *engine_side;
+                self.start_game(game);
+
+                Ok(Reply::Responses(vec![Response::Ack {
+                    request: request.name().to_string(),
+                }]))
+            }
+            Request::SetPosition {
+                start_pos,
+                position,
+                moves,
+            } => {
+                let mut game = new_game(request, start_pos, position.as_deref())?;
+                for (ply, &m) in moves.iter().enumerate() {
+                    game.play(m).map_err(|e| Response::Error {
+                        code: ErrorCode::IllegalMove,
+                        message: format!("Move {} of the list: {}", ply + 1, e),
+                        request: Some(request.name().to_string()),
+                        mov: Some(m),
+                    })?;
+                }
+
+                self.start_game(game);
+                Ok(Reply::Responses(vec![self.state()]))
+            }
+            Request::Undo { count } => {
+                let count = count.unwrap_or(1);
+                let ply = self.game.ply().checked_sub(count).ok_or_else(|| {
+                    Response::error(
+                        ErrorCode::InvalidRequest,
+                        format!(
+                            "Can't undo {} moves, only {} have been played",
+                            count,
+                            self.game.ply()
+                        ),
+                        Some(request),
+                    )
+                })?;
+
+                self.cancel_search();
+                self.game.jump_to(ply);
+                Ok(Reply::Responses(vec![self.state()]))
+            }
+            Request::GetState => Ok(Reply::Responses(vec![self.state()])),
+            Request::NewGame | Request::Resume | Request::CloseGame => Err(Response::error(
+                ErrorCode::InvalidRequest,
+                format!("{} is handled by the server, not by a game", request.name()),
+                Some(request),
+            )),
+            Request::Analyze { top, difficulty } => {
+                if self.game.board().is_game_over() {
+                    return Ok(Reply::Responses(vec![self.game_over()]));
+                }
+
+                let kind = SearchKind::Analyze {
+                    top: top.unwrap_or(3),
+                };
+                let board = self.game.board().clone();
+                Ok(Reply::Search(self.start_analysis(
+                    kind,
+                    board,
+                    difficulty.as_ref(),
+                )))
+            }
+            Request::Evaluate {
+                position,
+                difficulty,
+            } => {
+                let board = match position {
+                    Some(position) => BoardState::from_notation(position).map_err(|e| {
+                        Response::error(
+                            ErrorCode::InvalidPosition,
+                            format!("Invalid position: {}", e),
+                            Some(request),
+                        )
+                    })?,
+                    None => self.game.board().clone(),
+                };
+
+                // Nothing to search once the game is over
+                if board.is_game_over() {
+                    let win_probability = match (board.game_result(), board.player) {
+                        (GameResult::Draw, _) => 0.5,
+                        (GameResult::PlayerAWon, Player::White)
+                        | (GameResult::PlayerBWon, Player::Black) => 1.,
+                        _ => 0.,
+                    };
+                    return Ok(Reply::Responses(vec![Response::Evaluation {
+                        is_final: true,
+                        iterations: 0,
+                        player: board.player,
+                        win_probability,
+                    }]));
+                }
+
+                Ok(Reply::Search(self.start_analysis(
+                    SearchKind::Evaluate,
+                    board,
+                    difficulty.as_ref(),
+                )))
+            }
+            Request::FindMove { mov } => {
+                if let Some(last_move) = *mov {
+                    if self.engine_side == Some(self.game.board().player) {
+                        return Err(Response::Error {
+                            code: ErrorCode::WrongTurn,
+                            message: "It's the engine's turn, not the client's".to_string(),
+                            request: Some(request.name().to_string()),
+                            mov: Some(last_move),
+                        });
+                    }
+                    self.game.play(last_move).map_err(|e| Response::Error {
+                        code: ErrorCode::IllegalMove,
+                        message: e.to_string(),
+                        request: Some(request.name().to_string()),
+                        mov: Some(last_move),
+                    })?;
+                    log::debug!("Client played {}", Move::to_notation(last_move));
+                }
+                self.ponder_next = false;
+
+                if self.game.board().is_game_over() {
+                    if let Some(search) = &self.search {
+                        search.stop.store(true, Ordering::Relaxed);
+                    }
+                    return Ok(Reply::Responses(vec![self.game_over()]));
+                }
+                if self
+                    .engine_side
+                    .is_some_and(|side| side != self.game.board().player)
+                {
+                    return Err(Response::error(
+                        ErrorCode::WrongTurn,
+                        "It's the client's turn, send their move",
+                        Some(request),
+                    ));
+                }
+
+                let stop = Arc::new(AtomicBool::new(false));
+                match &self.search {
+                    // The ponder search has the tree, so the move waits for it to stop
+                    Some(search) => {
+                        search.stop.store(true, Ordering::Relaxed);
+                        self.queued_move = Some(stop);
+                        Ok(Reply::Responses(Vec::new()))
+                    }
+                    None => Ok(Reply::Search(self.start_search(stop, SearchKind::Move))),
+                }
+            }
+            Request::Stop => {
+                let stop = match (&self.search, &self.queued_move) {
+                    (_, Some(stop)) => stop,
+                    (Some(search), None) => &search.stop,
+                    (None, None) => {
+                        return Ok(Reply::Responses(vec![Response::Ack {
+                            request: request.name().to_string(),
+                        }]));
+                    }
+                };
+                stop.store(true, Ordering::Relaxed);
+
+                // The move or analysis the search finishes with is the reply. Pondering just
+                // stops.
+                if self.is_searching() {
+                    Ok(Reply::Responses(Vec::new()))
+                } else {
+                    Ok(Reply::Responses(vec![Response::Ack {
+                        request: request.name().to_string(),
+                    }]))
+                }
+            }
+        }
+    }
+
+    // Abandon whatever the engine was doing, and play `game` from now on
+    fn start_game(&mut self, game: Game) {
+        self.cancel_search();
+        self.game = game;
+        self.game
+            .metadata_mut()
+            .insert("difficulty".to_string(), self.difficulty.clone());
+    }
+
+    fn state(&self) -> Response {
+        let board = self.game.board();
+
+        Response::State {
+            start_pos: board.start_position,
+            initial_position: self.game.record().initial_position.clone(),
+            moves: self.game.moves().to_vec(),
+            position: board.to_notation(),
+            player: board.player,
+            engine_side: self.engine_side,
+            score: board.score(),
+            result: board.game_result(),
+        }
+    }
+
+    /// Play the engine's move from a search handed out by `handle` or `next_search`, and reply
+    /// with it, or reply with an analysis. Abandoned searches get no reply.
+    pub fn finish_search(&mut self, outcome: SearchOutcome) -> Vec<Response> {
+        let Some(search) = self.search.take_if(|search| search.id == outcome.id) else {
+            let suspended = self.suspended_search.take_if(|&mut id| id == outcome.id);
+            if self.eval.is_none() {
+                let mut eval = outcome.eval;
+                if suspended.is_none() || outcome.best_move.is_err() {
+                    eval.clear();
+                }
+                self.eval = Some(eval);
+            }
+            return Vec::new();
+        };
+
+        // Analyses have their own tree, and the engine's is still here
+        if let SearchKind::Analyze { .. } | SearchKind::Evaluate = search.kind {
+            return match (outcome.report, outcome.best_move) {
+                (Some(report), _) => vec![report],
+                (None, Err(message)) => {
+                    vec![Response::error(ErrorCode::Internal, message, None)]
+                }
+                (None, Ok(_)) => Vec::new(),
+            };
+        }
+
+        // A search without a move may have left the tree in any state, so start afresh
+        if outcome.best_move.is_ok() {
+            self.eval = Some(outcome.eval);
+        } else {
+            self.tree_ply = None;
+        }
+
+        let ponder = search.kind == SearchKind::Ponder;
+        let best_move = match (outcome.best_move, ponder) {
+            (Ok(_), true) => {
+                log::debug!("Pondered {} playouts", outcome.stats.plays);
+                return Vec::new();
+            }
+            (Err(message), true) => {
+                log::warn!("Pondering failed: {}", message);
+                return Vec::new();
+            }
+            (Ok(best_move), false) => best_move,
+            (Err(message), false) => {
+                return vec![Response::Error {
+                    code: ErrorCode::Internal,
+                    message,
+                    request: Some("findMove".to_string()),
+                    mov: None,
+                }];
+            }
+        };
+
+        if let Err(e) =
+            self.game
+                .play_with_stats(best_move, Some(outcome.think_time_ms), Some(outcome.stats))
+        {
+            return vec![Response::error(
+                ErrorCode::Internal,
+                format!("Engine chose an illegal move: {}", e),
+                None,
+            )];
+        }
+        log::info!("Engine played {}", Move::to_notation(best_move));
+
+        let mut responses = vec![Response::Move {
+            mov: best_move,
+            notation: Move::to_notation(best_move),
+        }];
+        if self.game.board().is_game_over() {
+            responses.push(self.game_over());
+        } else {
+            self.ponder_next = self.ponder;
+        }
+        responses
+    }
+
+    fn game_over(&mut self) -> Response {
+        let result = self.game.annotate_result();
+        match serde_json::to_string(self.game.record()) {
+            Ok(json) => log::info!("Game record: {}", json),
+            Err(e) => log::error!("Failed to serialize game: {}", e),
+        }
+
+        Response::GameOver {
+            result,
+            score: self.game.board().score(),
+        }
+    }
+}
+
+// A new game for `init` or `setPosition`, from the empty board or a set-up position
+fn new_game(request: &Request, start_pos: &str, position: Option<&str>) -> Result<Game, Response> {
+    let start_position = StartPosition::from_name(start_pos).ok_or_else(|| {
+        Response::error(
+            ErrorCode::InvalidStartPosition,
+            format!("Unknown start position '{}'", start_pos),
+            Some(request),
+        )
+    })?;
+
+    let Some(position) = position else {
+        return Ok(Game::new(start_position));
+    };
+
+    let board = BoardState::from_notation(position).map_err(|e| {
+        Response::error(
+            ErrorCode::InvalidPosition,
+            format!("Invalid position: {}", e),
+            Some(request),
+        )
+    })?;
+    if board.start_position != start_position {
+        return Err(Response::error(
+            ErrorCode::InvalidPosition,
+            "Position doesn't match the start position",
+            Some(request),
+        ));
+    }
+
+    Ok(Game::from_position(board))
+}
@@ -0,0 +1,340 @@
+//! Symmetries of the board.
+//!
+//! The 14x14 board has eight symmetries, but only the four that map the pair of start squares
+//! onto itself preserve the rules: the identity, the reflections in the two diagonals and the
+//! half turn. Depending on the start position, a symmetry either keeps each start square in
+//! place or swaps them, in which case the two players' pieces have to be swapped as well (a
+//! "colour swap"). For example, the Corner start is symmetric under the main diagonal
+//! reflection as is, and under the anti-diagonal reflection combined with a colour swap.
+//!
+//! A colour-swapped position is equivalent with the roles reversed: the side to move is
+//! swapped too, and the result of the game is mirrored.
+
+use std::fmt;
+
+use once_cell::sync::Lazy;
+
+use crate::bitboard::Bitboard;
+use crate::board::{
+    BoardState, Coord, Player, PositionError, StartPosition, get_start_position_coord,
+};
+use crate::movegen::{Move, NULL_MOVE, ORIENTATION_DATA};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Symmetry {
+    Identity,
+    /// Reflection in the main diagonal, (x, y) -> (y, x)
+    Transpose,
+    /// Reflection in the anti-diagonal, (x, y) -> (13 - y, 13 - x)
+    AntiTranspose,
+    /// Half turn, (x, y) -> (13 - x, 13 - y)
+    Rotate180,
+}
+
+// For each symmetry, piece and orientation, the orientation of the transformed piece
+static ORIENTATION_MAP: Lazy<Vec<Vec<Vec<u8>>>> = Lazy::new(|| {
+    Symmetry::ALL
+        .iter()
+        .map(|symmetry| {
+            ORIENTATION_DATA
+                .iter()
+                .map(|orientations| {
+                    let normalized: Vec<Vec<Coord>> = orientations
+                        .iter()
+                        .map(|tiles| normalize(tiles.iter().map(|&c| symmetry.apply(c))))
+                        .collect();
+
+                    normalized
+                        .iter()
+                        .map(|transformed| {
+                            orientations
+                                .iter()
+                                .position(|tiles| &normalize(tiles.iter().copied()) == transformed)
+                                .expect("Orientations aren't closed under symmetry")
+                                as u8
+                        })
+                        .collect()
+                })
+                .collect()
+        })
+        .collect()
+});
+
+// Shift a set of tiles so that its bounding box starts at (0, 0), and sort it
+fn normalize(tiles: impl Iterator<Item = Coord>) -> Vec<Coord> {
+    let tiles: Vec<Coord> = tiles.collect();
+    let min_x = tiles.iter().map(|c| c.x).min().unwrap_or(0);
+    let min_y = tiles.iter().map(|c| c.y).min().unwrap_or(0);
+
+    let mut normalized: Vec<Coord> = tiles
+        .iter()
+        .map(|c| Coord {
+            x: c.x - min_x,
+            y: c.y - min_y,
+        })
+        .collect();
+    normalized.sort();
+    normalized
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 4] = [
+        Symmetry::Identity,
+        Symmetry::Transpose,
+        Symmetry::AntiTranspose,
+        Symmetry::Rotate180,
+    ];
+
+    /// Apply the symmetry to a square of the board.
+    pub fn apply(&self, c: Coord) -> Coord {
+        match self {
+            Symmetry::Identity => c,
+            Symmetry::Transpose => Coord { x: c.y, y: c.x },
+            Symmetry::AntiTranspose => Coord {
+                x: 13 - c.y,
+                y: 13 - c.x,
+            },
+            Symmetry::Rotate180 => Coord {
+                x: 13 - c.x,
+                y: 13 - c.y,
+            },
+        }
+    }
+
+    /// All of the symmetries are their own inverse.
+    pub fn inverse(&self) -> Symmetry {
+        *self
+    }
+
+    /// Whether the symmetry swaps the start squares of `start_position`. `None` if it doesn't
+    /// map the start squares onto each other at all, so isn't a symmetry of the game.
+    pub fn swaps_colours(&self, start_position: StartPosition) -> Option<bool> {
+        let (start_a, start_b) = get_start_position_coord(start_position);
+
+        match (self.apply(start_a), self.apply(start_b)) {
+            (a, b) if a == start_a && b == start_b => Some(false),
+            (a, b) if a == start_b && b == start_a => Some(true),
+            _ => None,
+        }
+    }
+}
+
+/// The symmetries of a start position, with whether each one swaps colours.
+pub fn symmetries(start_position: StartPosition) -> Vec<(Symmetry, bool)> {
+    Symmetry::ALL
+        .iter()
+        .filter_map(|&symmetry| {
+            symmetry
+                .swaps_colours(start_position)
+                .map(|swap| (symmetry, swap))
+        })
+        .collect()
+}
+
+/// Apply a symmetry to a packed move, remapping its orientation and anchor square, and swapping
+/// its player if `swap_colours` is set. The null move maps to itself.
+pub fn transform_move(m: u32, symmetry: Symmetry, swap_colours: bool) -> u32 {
+    if m == NULL_MOVE {
+        return NULL_MOVE;
+    }
+
+    let mov = Move::unpack(m);
+    let tiles = &ORIENTATION_DATA[mov.movetype as usize][mov.orientation as usize];
+
+    let transformed = tiles.iter().map(|t| {
+        symmetry.apply(Coord {
+            x: mov.x + t.x,
+            y: mov.y + t.y,
+        })
+    });
+    let x = transformed.clone().map(|c| c.x).min().unwrap();
+    let y = transformed.map(|c| c.y).min().unwrap();
+
+    Move {
+        orientation: ORIENTATION_MAP[symmetry as usize][mov.movetype as usize]
+            [mov.orientation as usize],
+        y,
+        x,
+        movetype: mov.movetype,
+        player: if swap_colours {
+            1 - mov.player
+        } else {
+            mov.player
+        },
+    }
+    .pack()
+}
+
+fn transform_bit_board(bit_board: &Bitboard, symmetry: Symmetry) -> Bitboard {
+    Bitboard::from_squares(bit_board.squares().map(|c| symmetry.apply(c)))
+}
+
+/// Why `transform_board` couldn't transform a position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymmetryError {
+    /// The symmetry doesn't map the start position's start squares onto themselves
+    NotASymmetry(Symmetry, StartPosition),
+    /// The image was rejected, which only happens if the position itself is invalid
+    InvalidPosition(PositionError),
+}
+
+impl fmt::Display for SymmetryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SymmetryError::NotASymmetry(symmetry, start_position) => write!(
+                f,
+                "{:?} isn't a symmetry of the {:?} start position",
+                symmetry, start_position
+            ),
+            SymmetryError::InvalidPosition(e) => write!(f, "invalid position: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SymmetryError {}
+
+// The side to move, bitboards and remaining pieces of a position, which is all a symmetry
+// changes. Ordered like the fields, so the canonical form can be picked without building boards.
+type PositionKey = (u8, Bitboard, Bitboard, u32, u32);
+
+fn position_key(board: &BoardState) -> PositionKey {
+    (
+        board.player as u8,
+        board.player_a_bit_board,
+        board.player_b_bit_board,
+        board.player_a_remaining,
+        board.player_b_remaining,
+    )
+}
+
+fn image_key(board: &BoardState, symmetry: Symmetry, swap: bool) -> PositionKey {
+    let player_a_bit_board = transform_bit_board(&board.player_a_bit_board, symmetry);
+    let player_b_bit_board = transform_bit_board(&board.player_b_bit_board, symmetry);
+
+    if swap {
+        (
+            board.player.other() as u8,
+            player_b_bit_board,
+            player_a_bit_board,
+            board.player_b_remaining,
+            board.player_a_remaining,
+        )
+    } else {
+        (
+            board.player as u8,
+            player_a_bit_board,
+            player_b_bit_board,
+            board.player_a_remaining,
+            board.player_b_remaining,
+        )
+    }
+}
+
+fn board_from_key(board: &BoardState, key: PositionKey) -> Result<BoardState, SymmetryError> {
+    let (player, player_a_bit_board, player_b_bit_board, player_a_remaining, player_b_remaining) =
+        key;
+    let player = if player == Player::White as u8 {
+        Player::White
+    } else {
+        Player::Black
+    };
+
+    let mut transformed = BoardState::from_bitboards(
+        board.start_position,
+        player_a_bit_board,
+        player_b_bit_board,
+        player_a_remaining,
+        player_b_remaining,
+        player,
+    )
+    .map_err(SymmetryError::InvalidPosition)?;
+    transformed.null_move_counter = board.null_move_counter;
+
+    Ok(transformed)
+}
+
+/// Apply a symmetry to a position, swapping the players if the symmetry swaps colours for the
+/// board's start position.
+pub fn transform_board(
+    board: &BoardState,
+    symmetry: Symmetry,
+) -> Result<BoardState, SymmetryError> {
+    let swap = symmetry
+        .swaps_colours(board.start_position)
+        .ok_or(SymmetryError::NotASymmetry(symmetry, board.start_position))?;
+
+    board_from_key(board, image_key(board, symmetry, swap))
+}
+
+/// The canonical representative of a position among its symmetric images, along with the
+/// symmetry that maps `board` onto it. Positions that are images of each other have the same
+/// canonical form (possibly with the colours swapped, see the module docs).
+pub fn canonical_form(board: &BoardState) -> Result<(BoardState, Symmetry), SymmetryError> {
+    let mut best_key = position_key(board);
+    let mut best_symmetry = Symmetry::Identity;
+
+    for (symmetry, swap) in symmetries(board.start_position) {
+        if symmetry == Symmetry::Identity {
+            continue;
+        }
+
+        let key = image_key(board, symmetry, swap);
+        if key < best_key {
+            best_key = key;
+            best_symmetry = symmetry;
+        }
+    }
+
+    // only the chosen image needs its move caches
+    if best_symmetry == Symmetry::Identity {
+        return Ok((board.clone(), best_symmetry));
+    }
+    Ok((board_from_key(board, best_key)?, best_symmetry))
+}
+
+/// The symmetries that map `board` onto itself without swapping colours (always including the
+/// identity). A colour swap also swaps the side to move, so it can never fix a position.
+pub fn position_symmetries(board: &BoardState) -> Vec<Symmetry> {
+    symmetries(board.start_position)
+        .into_iter()
+        .filter(|&(symmetry, swap)| {
+            !swap
+                && transform_bit_board(&board.player_a_bit_board, symmetry)
+                    == board.player_a_bit_board
+                && transform_bit_board(&board.player_b_bit_board, symmetry)
+                    == board.player_b_bit_board
+        })
+        .map(|(symmetry, _)| symmetry)
+        .collect()
+}
+
+/// The moves equivalent to `m` in `board` under the position's own symmetries, including `m`.
+pub fn equivalent_moves(board: &BoardState, m: u32) -> Vec<u32> {
+    let mut moves: Vec<u32> = position_symmetries(board)
+        .into_iter()
+        .map(|symmetry| transform_move(m, symmetry, false))
+        .collect();
+    moves.sort_unstable();
+    moves.dedup();
+    moves
+}
+
+/// Keep one representative (the smallest packed move) of each class of moves that are
+/// equivalent under the position's own symmetries. If the position isn't symmetric, all of
+/// `moves` are kept.
+pub fn unique_moves(board: &BoardState, moves: &[u32]) -> Vec<u32> {
+    let symmetries = position_symmetries(board);
+    if symmetries.len() == 1 {
+        return moves.to_vec();
+    }
+
+    moves
+        .iter()
+        .copied()
+        .filter(|&m| {
+            symmetries
+                .iter()
+                .all(|&symmetry| transform_move(m, symmetry, false) >= m)
+        })
+        .collect()
+}
@@ -0,0 +1,124 @@
+use blok_rs::analysis::{reachable_squares, territory};
+use blok_rs::bitboard::Bitboard;
+use blok_rs::board::{BoardState, Coord, Player, StartPosition};
+use blok_rs::movegen::{self, Move, NULL_MOVE, ORIENTATION_DATA};
+use rand::prelude::*;
+
+fn covers(bit_board: &Bitboard, m: u32) -> bool {
+    let mov = Move::unpack(m);
+    ORIENTATION_DATA[mov.movetype as usize][mov.orientation as usize]
+        .iter()
+        .all(|t| {
+            bit_board.get(Coord {
+                x: mov.x + t.x,
+                y: mov.y + t.y,
+            })
+        })
+}
+
+fn random_game(start_position: StartPosition, seed: u64) -> (Vec<BoardState>, Vec<u32>) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut board = BoardState::new(start_position);
+    let mut positions = Vec::new();
+    let mut moves = Vec::new();
+
+    while !board.is_game_over() {
+        let m = *movegen::generate_moves(&board).choose(&mut rng).unwrap();
+        positions.push(board.clone());
+        moves.push(m);
+        board.do_move(m);
+    }
+    positions.push(board);
+
+    (positions, moves)
+}
+
+#[test]
+pub fn empty_board_is_all_reachable() {
+    for start_position in [
+        StartPosition::Middle,
+        StartPosition::Corner,
+        StartPosition::MiddleBlokee,
+    ] {
+        let counts = territory(&BoardState::new(start_position)).counts();
+        assert_eq!(counts.player_a, 196);
+        assert_eq!(counts.player_b, 196);
+        assert_eq!(counts.contested, 196);
+        assert_eq!(counts.dead, 0);
+    }
+}
+
+#[test]
+pub fn later_moves_stay_inside_territory() {
+    for seed in 0..10 {
+        let start_position = if seed % 2 == 0 {
+            StartPosition::Corner
+        } else {
+            StartPosition::Middle
+        };
+        let (positions, moves) = random_game(start_position, seed);
+
+        for (ply, board) in positions.iter().enumerate() {
+            let reachable_a = reachable_squares(board, Player::White);
+            let reachable_b = reachable_squares(board, Player::Black);
+
+            for &m in moves[ply..].iter().filter(|&&m| m != NULL_MOVE) {
+                let reachable = if Move::get_player(m) == 0 {
+                    &reachable_a
+                } else {
+                    &reachable_b
+                };
+                assert!(
+                    covers(reachable, m),
+                    "{} at ply {} isn't inside the territory at ply {}\n{}",
+                    Move::to_notation(m),
+                    moves.iter().position(|&n| n == m).unwrap(),
+                    ply,
+                    board.display_territory()
+                );
+            }
+        }
+    }
+}
+
+#[test]
+pub fn territory_partitions_empty_squares() {
+    let (positions, _) = random_game(StartPosition::Corner, 42);
+
+    for board in &positions {
+        let territory = territory(board);
+        let counts = territory.counts();
+        let occupied = board.occupied().count();
+
+        assert_eq!(
+            counts.exclusive_a + counts.exclusive_b + counts.contested + counts.dead + occupied,
+            196
+        );
+        assert_eq!(counts.player_a, counts.exclusive_a + counts.contested);
+        assert!(!territory.player_a.intersects(&board.occupied()));
+        assert!(!territory.player_b.intersects(&board.occupied()));
+        assert!(
+            !territory
+                .dead
+                .intersects(&(territory.player_a | territory.player_b))
+        );
+    }
+
+    // nothing is left to reach once the game is over
+    let counts = territory(positions.last().unwrap()).counts();
+    assert_eq!(counts.player_a + counts.player_b, 0);
+}
+
+#[test]
+pub fn overlay_rendering() {
+    let (positions, _) = random_game(StartPosition::Corner, 7);
+    let board = &positions[positions.len() / 2];
+
+    let plain = board.to_string();
+    let overlay = board.display_territory().to_string();
+    assert_eq!(plain.lines().count(), overlay.lines().count());
+    assert!(overlay.contains('x') || overlay.contains('-') || overlay.contains('='));
+
+    let coloured = board.display_ansi().with_territory().to_string();
+    assert!(coloured.contains("\x1b["));
+}
@@ -0,0 +1,105 @@
+use blok_rs::bitboard::Bitboard;
+use blok_rs::board::{BoardState, Coord, StartPosition};
+
+fn square(x: u8, y: u8) -> Coord {
+    Coord { x, y }
+}
+
+#[test]
+pub fn set_get_and_count() {
+    let mut bitboard = Bitboard::EMPTY;
+    assert!(bitboard.is_empty());
+
+    bitboard.set(square(0, 0));
+    bitboard.set(square(13, 13));
+    bitboard.set(square(5, 7));
+    assert_eq!(bitboard.count(), 3);
+    assert!(bitboard.get(square(5, 7)));
+    assert!(!bitboard.get(square(7, 5)));
+    assert!(!bitboard.get(square(14, 0)));
+
+    assert_eq!(
+        bitboard.squares().collect::<Vec<_>>(),
+        vec![square(0, 0), square(5, 7), square(13, 13)]
+    );
+
+    bitboard.clear(square(0, 0));
+    assert_eq!(bitboard.count(), 2);
+    assert_eq!(Bitboard::FULL.count(), 196);
+    assert_eq!((!Bitboard::EMPTY), Bitboard::FULL);
+    assert!(!Bitboard::FULL.has_bits_outside_board());
+}
+
+#[test]
+pub fn shifts_stay_on_the_board() {
+    let corner = Bitboard::from_square(square(0, 0));
+    assert!(corner.shift_left().is_empty());
+    assert!(corner.shift_up().is_empty());
+    assert_eq!(corner.shift_right(), Bitboard::from_square(square(1, 0)));
+    assert_eq!(corner.shift_down(), Bitboard::from_square(square(0, 1)));
+
+    let far_corner = Bitboard::from_square(square(13, 13));
+    assert!(far_corner.shift_right().is_empty());
+    assert!(far_corner.shift_down().is_empty());
+
+    assert_eq!(Bitboard::FULL.shift_right().count(), 182);
+    assert!(!Bitboard::FULL.shift_right().has_bits_outside_board());
+}
+
+#[test]
+pub fn neighbours_and_corners() {
+    let middle = Bitboard::from_square(square(5, 5));
+    assert_eq!(middle.edge_neighbours().count(), 4);
+    assert_eq!(middle.diagonal_neighbours().count(), 4);
+    assert_eq!(middle.expand().count(), 9);
+    assert_eq!(
+        middle.expand(),
+        middle | middle.edge_neighbours() | middle.diagonal_neighbours()
+    );
+
+    let corner = Bitboard::from_square(square(0, 0));
+    assert_eq!(corner.expand().count(), 4);
+
+    // an L tromino: (0,0), (0,1), (1,1)
+    let tiles = Bitboard::from_squares([square(0, 0), square(0, 1), square(1, 1)]);
+    let occupied = Bitboard::from_square(square(2, 0));
+    assert_eq!(
+        tiles
+            .corners(&Bitboard::EMPTY)
+            .squares()
+            .collect::<Vec<_>>(),
+        vec![square(2, 0), square(2, 2)]
+    );
+    assert_eq!(
+        tiles.corners(&occupied).squares().collect::<Vec<_>>(),
+        vec![square(2, 2)]
+    );
+}
+
+#[test]
+pub fn flood_fill_spreads_diagonally_within_the_mask() {
+    // a wall along column 3, with a gap that's only diagonally passable
+    let mut within = Bitboard::FULL;
+    for y in 0..14 {
+        within.clear(square(3, y));
+    }
+    let start = Bitboard::from_square(square(0, 0));
+    assert_eq!(start.flood_fill(&within).count(), 3 * 14);
+
+    within.set(square(3, 6));
+    assert_eq!(start.flood_fill(&within), within);
+}
+
+#[test]
+pub fn board_bitboards() {
+    let mut board = BoardState::new(StartPosition::Corner);
+    board.do_move(0);
+
+    let score = board.score();
+    assert_eq!(board.player_a_bit_board.count(), score.player_a);
+    assert_eq!(board.occupied(), board.player_a_bit_board);
+
+    // rows can still be indexed directly
+    assert_eq!(board.player_a_bit_board[0], 1);
+    assert_eq!(board.player_a_bit_board.rows()[0], 1);
+}
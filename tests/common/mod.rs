@@ -0,0 +1,28 @@
+//! Helpers shared by the integration tests. Each test crate uses only some of them.
+#![allow(dead_code)]
+
+use blok_rs::board::BoardState;
+use blok_rs::game::Game;
+use blok_rs::movegen;
+
+/// Play the smallest legal move `plies` times, or until the game is over, returning the moves
+/// played.
+pub fn play_min_moves(board: &mut BoardState, plies: usize) -> Vec<u32> {
+    let mut moves = Vec::new();
+    for _ in 0..plies {
+        if board.is_game_over() {
+            break;
+        }
+        let m = *movegen::generate_moves(board).iter().min().unwrap();
+        board.do_move(m);
+        moves.push(m);
+    }
+    moves
+}
+
+/// `play_min_moves` on a `Game`.
+pub fn play_min_game_moves(game: &mut Game, plies: usize) {
+    for m in play_min_moves(&mut game.board().clone(), plies) {
+        game.play(m).unwrap();
+    }
+}
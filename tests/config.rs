@@ -0,0 +1,188 @@
+use blok_rs::mcts::SearchBudget;
+use blok_rs::server::log::LogLevel;
+use blok_rs::server::{ConfigError, ServerConfig};
+
+#[test]
+pub fn defaults_match_the_old_server() {
+    let config = ServerConfig::from_args(Vec::<String>::new()).unwrap();
+    assert_eq!(config, ServerConfig::default());
+    assert_eq!(config.addr(), "127.0.0.1:8080");
+    assert!(config.budgets.is_empty());
+    assert_eq!(config.max_sessions, None);
+    assert_eq!(config.log_level, LogLevel::Info);
+}
+
+#[test]
+pub fn flags_set_every_option() {
+    let config = ServerConfig::from_args([
+        "--bind",
+        "0.0.0.0",
+        "--port=9001",
+        "--threads",
+        "2",
+        "--search-threads",
+        "3",
+        "--budget",
+        "hard=200000",
+        "--budget=blitz=750ms",
+        "--max-sessions",
+        "16",
+        "--session-timeout",
+        "30",
+        "--max-games",
+        "50",
+        "--max-games-per-connection=2",
+        "--log-level",
+        "debug",
+    ])
+    .unwrap();
+
+    assert_eq!(config.addr(), "0.0.0.0:9001");
+    assert_eq!(config.worker_threads, Some(2));
+    assert_eq!(config.max_concurrent_searches, 3);
+    assert_eq!(
+        config.budgets.get("hard"),
+        Some(&SearchBudget::Iterations(200_000))
+    );
+    assert_eq!(
+        config.budgets.get("blitz"),
+        Some(&SearchBudget::Millis(750))
+    );
+    assert_eq!(config.max_sessions, Some(16));
+    assert_eq!(config.session_timeout_secs, 30);
+    assert_eq!(config.max_games, 50);
+    assert_eq!(config.max_games_per_connection, 2);
+    assert_eq!(config.log_level, LogLevel::Debug);
+}
+
+#[test]
+pub fn bad_flags_are_reported() {
+    let error = |args: &[&str]| ServerConfig::from_args(args.iter().copied()).unwrap_err();
+
+    assert_eq!(
+        error(&["--colour", "blue"]),
+        ConfigError::UnknownFlag("--colour".to_string())
+    );
+    assert_eq!(
+        error(&["8080"]),
+        ConfigError::UnknownFlag("8080".to_string())
+    );
+    assert_eq!(
+        error(&["--port"]),
+        ConfigError::MissingValue("--port".to_string())
+    );
+    for args in [
+        ["--port", "99999"],
+        ["--threads", "0"],
+        ["--max-games", "0"],
+        ["--budget", "hard"],
+        ["--budget", "hard=fast"],
+        ["--budget", "=1000"],
+        ["--budget", "hard=0ms"],
+        ["--log-level", "loud"],
+    ] {
+        assert!(
+            matches!(error(&args), ConfigError::InvalidValue { .. }),
+            "{:?} should be invalid",
+            args
+        );
+    }
+}
+
+#[test]
+pub fn config_files_are_overridden_by_flags() {
+    let path = std::env::temp_dir().join(format!("blok-rs-config-{}.json", std::process::id()));
+    std::fs::write(
+        &path,
+        r#"{
+            "port": 9002,
+            "budgets": {"easy": {"iterations": 5000}, "hard": {"millis": 2000}},
+            "maxSessions": 4,
+            "logLevel": "warn"
+        }"#,
+    )
+    .unwrap();
+
+    let config = ServerConfig::from_args([
+        "--port".to_string(),
+        "9003".to_string(),
+        "--config".to_string(),
+        path.display().to_string(),
+        "--budget".to_string(),
+        "easy=100ms".to_string(),
+    ]);
+    std::fs::remove_file(&path).unwrap();
+    let config = config.unwrap();
+
+    // the flag wins wherever it's given
+    assert_eq!(config.port, 9003);
+    assert_eq!(config.bind, "127.0.0.1");
+    assert_eq!(config.budgets.get("easy"), Some(&SearchBudget::Millis(100)));
+    assert_eq!(
+        config.budgets.get("hard"),
+        Some(&SearchBudget::Millis(2000))
+    );
+    assert_eq!(config.max_sessions, Some(4));
+    assert_eq!(config.log_level, LogLevel::Warn);
+}
+
+#[test]
+pub fn bad_config_files_are_reported() {
+    assert!(matches!(
+        ServerConfig::from_file("/nonexistent/blok-rs.json"),
+        Err(ConfigError::Read { .. })
+    ));
+    assert!(ServerConfig::from_json(r#"{"port": 1, "colour": "blue"}"#).is_err());
+    assert!(ServerConfig::from_json(r#"{"budgets": {"hard": 1000}}"#).is_err());
+}
+
+#[test]
+pub fn zero_limits_in_config_files_are_rejected() {
+    let invalid = |setting: &str, value: &str| ConfigError::InvalidSetting {
+        setting: setting.to_string(),
+        value: value.to_string(),
+    };
+
+    for (json, error) in [
+        (r#"{"workerThreads": 0}"#, invalid("workerThreads", "0")),
+        (
+            r#"{"maxConcurrentSearches": 0}"#,
+            invalid("maxConcurrentSearches", "0"),
+        ),
+        (r#"{"maxSessions": 0}"#, invalid("maxSessions", "0")),
+        (r#"{"maxGames": 0}"#, invalid("maxGames", "0")),
+        (
+            r#"{"maxGamesPerConnection": 0}"#,
+            invalid("maxGamesPerConnection", "0"),
+        ),
+        (
+            r#"{"budgets": {"hard": {"iterations": 0}}}"#,
+            invalid("budgets.hard", r#"{"iterations":0}"#),
+        ),
+        (
+            r#"{"budgets": {"hard": {"millis": 0}}}"#,
+            invalid("budgets.hard", r#"{"millis":0}"#),
+        ),
+        (
+            r#"{"budgets": {"": {"iterations": 10}}}"#,
+            invalid("budgets.", r#"{"iterations":10}"#),
+        ),
+    ] {
+        assert_eq!(
+            ServerConfig::from_json(json).unwrap_err(),
+            error,
+            "{}",
+            json
+        );
+    }
+
+    // files are checked the same way
+    let path =
+        std::env::temp_dir().join(format!("blok-rs-zero-config-{}.json", std::process::id()));
+    std::fs::write(&path, r#"{"maxSessions": 0}"#).unwrap();
+    let config = ServerConfig::from_args(["--config".to_string(), path.display().to_string()]);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(config.unwrap_err(), invalid("maxSessions", "0"));
+
+    assert!(ServerConfig::from_json(r#"{"sessionTimeoutSecs": 0}"#).is_ok());
+}
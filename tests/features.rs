@@ -0,0 +1,95 @@
+use blok_rs::board::{BoardState, Player, StartPosition};
+use blok_rs::features::{OpponentMoves, move_features};
+use blok_rs::movegen::{self, NULL_MOVE};
+use rand::prelude::*;
+
+fn check_features(board: &BoardState, opponent_moves: &OpponentMoves, m: u32) {
+    let features = move_features(board, opponent_moves, m).unwrap();
+    let player = board.player;
+    let opponent = player.other();
+
+    let mut after = board.clone();
+    after.do_move(m);
+
+    let corners_before = board.bit_board(player).corners(&board.occupied());
+    let corners_after = after.bit_board(player).corners(&after.occupied());
+    let piece = *after.bit_board(player) ^ *board.bit_board(player);
+
+    assert_eq!(features.piece_size, piece.count());
+    assert_eq!(
+        features.new_corners,
+        (corners_after & !corners_before).count()
+    );
+    assert_eq!(
+        features.blocked_opponent_moves as usize,
+        board.legal_move_count(opponent) - after.legal_move_count(opponent)
+    );
+    assert_eq!(
+        features.blocked_opponent_corners as usize,
+        board.live_corner_count(opponent) - after.live_corner_count(opponent)
+    );
+    assert_eq!(
+        features.opponent_contact,
+        (piece.edge_neighbours() & *board.bit_board(opponent)).count()
+    );
+    assert_eq!(
+        features.edge_hugging,
+        piece
+            .squares()
+            .any(|c| c.x == 0 || c.y == 0 || c.x == 13 || c.y == 13)
+    );
+    match features.opponent_distance {
+        None => assert!(board.bit_board(opponent).is_empty()),
+        Some(distance) => {
+            assert!(distance >= 1);
+            assert_eq!(
+                distance == 1,
+                piece.expand().intersects(board.bit_board(opponent))
+            );
+        }
+    }
+}
+
+#[test]
+pub fn features_match_playing_the_move() {
+    for seed in 0..4 {
+        let start_position = if seed % 2 == 0 {
+            StartPosition::Corner
+        } else {
+            StartPosition::Middle
+        };
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut board = BoardState::new(start_position);
+
+        while !board.is_game_over() {
+            let moves = movegen::generate_moves(&board);
+            let opponent_moves = OpponentMoves::new(&board, board.player.other());
+            for &m in moves.iter().filter(|&&m| m != NULL_MOVE).step_by(7) {
+                check_features(&board, &opponent_moves, m);
+            }
+
+            board.do_move(*moves.choose(&mut rng).unwrap());
+        }
+    }
+}
+
+#[test]
+pub fn first_move_features() {
+    let board = BoardState::new(StartPosition::Corner);
+    let opponent_moves = OpponentMoves::new(&board, Player::Black);
+    assert_eq!(move_features(&board, &opponent_moves, NULL_MOVE), None);
+
+    let moves = movegen::generate_moves(&board);
+    let i1 = *moves
+        .iter()
+        .find(|&&m| movegen::Move::get_movetype(m) == 16)
+        .unwrap();
+    let features = move_features(&board, &opponent_moves, i1).unwrap();
+
+    assert_eq!(features.piece_size, 1);
+    assert_eq!(features.new_corners, 1);
+    assert_eq!(features.blocked_opponent_moves, 0);
+    assert_eq!(features.opponent_distance, None);
+    assert!(features.edge_hugging);
+    assert_eq!(features.centre_distance, 13.0);
+}
@@ -0,0 +1,92 @@
+use blok_rs::board::{BoardState, GameResult, StartPosition};
+use blok_rs::game::Game;
+use blok_rs::movegen::{self, IllegalMove};
+use blok_rs::record::SearchStats;
+
+mod common;
+
+use common::play_min_game_moves;
+
+#[test]
+pub fn history_and_jump_to_ply() {
+    let mut game = Game::new(StartPosition::Corner);
+    play_min_game_moves(&mut game, 10);
+    assert_eq!(game.ply(), 10);
+
+    let mut expected = BoardState::new(StartPosition::Corner);
+    for &m in &game.moves()[..4] {
+        expected.do_move(m);
+    }
+    assert_eq!(
+        game.position_at(4).unwrap().to_notation(),
+        expected.to_notation()
+    );
+    assert!(game.position_at(11).is_none());
+
+    assert!(game.jump_to(4));
+    assert_eq!(game.ply(), 4);
+    assert_eq!(game.record().move_info.len(), 4);
+    assert_eq!(game.board().to_notation(), expected.to_notation());
+    assert!(!game.jump_to(5));
+
+    play_min_game_moves(&mut game, 3);
+    assert_eq!(game.ply(), 7);
+    assert!(game.validate().is_ok());
+}
+
+#[test]
+pub fn rejects_illegal_moves() {
+    let mut game = Game::new(StartPosition::Corner);
+    play_min_game_moves(&mut game, 2);
+
+    let repeated = game.moves()[0];
+    assert_eq!(game.play(repeated), Err(IllegalMove::PieceAlreadyUsed));
+    assert_eq!(game.ply(), 2);
+}
+
+#[test]
+pub fn records_stats_and_result() {
+    let mut game = Game::new(StartPosition::Middle);
+    let m = movegen::generate_moves(game.board())[0];
+    game.play_with_stats(m, Some(12), Some(SearchStats { plays: 10, wins: 7 }))
+        .unwrap();
+
+    let info = game.record().move_info[0];
+    assert_eq!(info.think_time_ms, Some(12));
+    assert_eq!(info.search, Some(SearchStats { plays: 10, wins: 7 }));
+    assert!(info.timestamp_ms > 0);
+
+    // the engine's own moves are recorded the same way, without being checked again
+    let m = movegen::generate_moves(game.board())[0];
+    game.play_engine_move(m, Some(5), None);
+    assert_eq!(game.moves(), &[game.moves()[0], m]);
+    assert_eq!(game.record().move_info[1].think_time_ms, Some(5));
+    assert!(game.validate().is_ok());
+
+    play_min_game_moves(&mut game, 200);
+    let result = game.annotate_result();
+    assert_ne!(result, GameResult::InProgress);
+    assert_eq!(game.record().result, Some(result));
+    assert_eq!(game.record().score, Some(game.board().score()));
+
+    let loaded = Game::from_record(game.to_record()).unwrap();
+    assert_eq!(loaded.board().to_notation(), game.board().to_notation());
+    assert_eq!(loaded.record(), game.record());
+}
+
+#[test]
+pub fn game_from_position() {
+    let mut setup = Game::new(StartPosition::Corner);
+    play_min_game_moves(&mut setup, 4);
+
+    let mut game = Game::from_position(setup.board().clone());
+    play_min_game_moves(&mut game, 2);
+    assert_eq!(game.ply(), 2);
+    assert_eq!(
+        game.position_at(0).unwrap().to_notation(),
+        setup.board().to_notation()
+    );
+
+    let loaded = Game::from_record(game.to_record()).unwrap();
+    assert_eq!(loaded.board().to_notation(), game.board().to_notation());
+}
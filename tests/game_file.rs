@@ -0,0 +1,166 @@
+use blok_rs::board::{GameResult, StartPosition};
+use blok_rs::game::Game;
+use blok_rs::game_file::{GameFileError, date_string, read_game, read_games, write_games};
+use blok_rs::movegen;
+use blok_rs::record::{GameRecord, SearchStats};
+
+mod common;
+
+// Play the smallest move like `common::play_min_moves`, with made up search stats
+fn play_min_moves_with_stats(game: &mut Game, plies: usize) {
+    let moves = common::play_min_moves(&mut game.board().clone(), plies);
+    for (ply, m) in moves.into_iter().enumerate() {
+        let stats = SearchStats {
+            plays: 100 + ply,
+            wins: 50,
+        };
+        game.play_with_stats(m, Some(ply as u64 * 10), Some(stats))
+            .unwrap();
+    }
+}
+
+#[test]
+pub fn finished_game_round_trip() {
+    let mut game = Game::new(StartPosition::Corner);
+    game.metadata_mut()
+        .insert("playerA".to_string(), "hce \"latest\"".to_string());
+    game.metadata_mut()
+        .insert("date".to_string(), "2026-10-18".to_string());
+    play_min_moves_with_stats(&mut game, 200);
+    game.annotate_result();
+
+    let text = game.record().to_game_file().unwrap();
+    assert!(text.starts_with("[startPosition \"corner\"]\n"));
+    assert!(text.contains("[playerA \"hce \\\"latest\\\"\"]"));
+    assert!(text.contains("1. "));
+    assert!(text.lines().all(|line| line.len() <= 80));
+
+    let read = read_game(&text).unwrap();
+    assert_eq!(&read, game.record());
+    assert_ne!(read.result, Some(GameResult::InProgress));
+}
+
+#[test]
+pub fn unfinished_game_from_position_round_trip() {
+    let mut game = Game::new(StartPosition::Middle);
+    play_min_moves_with_stats(&mut game, 3);
+    let mut game = Game::from_position(game.board().clone());
+    game.play(*movegen::generate_moves(game.board()).iter().max().unwrap())
+        .unwrap();
+
+    let text = game.record().to_game_file().unwrap();
+    assert!(text.contains("[initialPosition "));
+    assert!(text.trim_end().ends_with('*'));
+    assert_eq!(&read_game(&text).unwrap(), game.record());
+}
+
+#[test]
+pub fn several_games_in_one_file() {
+    let mut records = Vec::new();
+    for plies in [0, 5, 12] {
+        let mut game = Game::new(StartPosition::Corner);
+        play_min_moves_with_stats(&mut game, plies);
+        records.push(game.to_record());
+    }
+
+    assert_eq!(
+        read_games(&write_games(&records).unwrap()).unwrap(),
+        records
+    );
+}
+
+#[test]
+pub fn hand_written_game() {
+    let text = "[startPosition \"corner\"]\n[event \"test\"]\n\n\
+                1. I5-0@a1 {think=20} I5-0@n10\n*\n";
+    let record = read_game(text).unwrap();
+
+    assert_eq!(record.start_position, StartPosition::Corner);
+    assert_eq!(record.metadata["event"], "test");
+    assert_eq!(record.moves.len(), 2);
+    assert_eq!(record.move_info[0].think_time_ms, Some(20));
+    assert_eq!(record.move_info[1].think_time_ms, None);
+    assert_eq!(record.result, None);
+}
+
+#[test]
+pub fn invalid_files_are_rejected() {
+    let illegal = "[startPosition \"corner\"]\n1. I5-0@e5 *\n";
+    assert!(matches!(
+        read_game(illegal),
+        Err(GameFileError::Move { ply: 0, .. })
+    ));
+
+    assert_eq!(
+        read_game("1. I5-0@a1 *"),
+        Err(GameFileError::MissingStartPosition)
+    );
+    assert_eq!(
+        read_game("[startPosition \"corner\"]\n1. I5-0@a1"),
+        Err(GameFileError::UnterminatedGame)
+    );
+    assert_eq!(
+        read_game("[startPosition \"corner\"]\n1. I5-0@a1 {think=x} *"),
+        Err(GameFileError::InvalidComment("think=x".to_string()))
+    );
+    assert_eq!(
+        read_game("[startPosition \"corner\"\n*"),
+        Err(GameFileError::UnterminatedTag)
+    );
+    assert_eq!(
+        read_game("[startPosition \"corner\"]\n[result \"1-0\"]\n0-1"),
+        Err(GameFileError::InvalidResult("0-1".to_string()))
+    );
+    assert!(matches!(
+        read_game("[startPosition \"edge\"]\n*"),
+        Err(GameFileError::InvalidStartPosition(_))
+    ));
+
+    let empty = GameRecord::new(StartPosition::Corner);
+    assert_eq!(read_game(&empty.to_game_file().unwrap()).unwrap(), empty);
+
+    assert_eq!(read_game(""), Err(GameFileError::NoGame));
+    assert_eq!(read_game(" \n"), Err(GameFileError::NoGame));
+}
+
+#[test]
+pub fn metadata_names_round_trip_or_are_rejected() {
+    let mut record = GameRecord::new(StartPosition::Corner);
+    for name in ["event", "player_a", "engine-version", "v1.2"] {
+        record
+            .metadata
+            .insert(name.to_string(), "x ] \"y\"".to_string());
+    }
+    let text = record.to_game_file().unwrap();
+    assert_eq!(read_game(&text).unwrap(), record);
+
+    for name in [
+        "",
+        "two words",
+        "tab\tname",
+        "bracket]",
+        "quote\"",
+        "result",
+        "score",
+        "startPosition",
+        "initialPosition",
+    ] {
+        let mut record = GameRecord::new(StartPosition::Corner);
+        record.metadata.insert(name.to_string(), "x".to_string());
+        assert_eq!(
+            record.to_game_file(),
+            Err(GameFileError::InvalidMetadataName(name.to_string()))
+        );
+        assert_eq!(
+            write_games(&[record]),
+            Err(GameFileError::InvalidMetadataName(name.to_string()))
+        );
+    }
+}
+
+#[test]
+pub fn dates() {
+    assert_eq!(date_string(0), "1970-01-01");
+    assert_eq!(date_string(951_782_400_000), "2000-02-29");
+    assert_eq!(date_string(1_792_281_600_000), "2026-10-18");
+}
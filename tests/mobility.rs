@@ -0,0 +1,90 @@
+use blok_rs::board::{BoardState, Player, StartPosition};
+use blok_rs::movegen::{self, NULL_MOVE};
+use rand::prelude::*;
+
+// The moves `player` would have if it were their turn, from a freshly rebuilt board
+fn moves_from_scratch(board: &BoardState, player: Player) -> (Vec<u32>, usize) {
+    let mut rebuilt = BoardState::from_bitboards(
+        board.start_position,
+        board.player_a_bit_board,
+        board.player_b_bit_board,
+        board.player_a_remaining,
+        board.player_b_remaining,
+        player,
+    )
+    .unwrap();
+    rebuilt.null_move_counter = board.null_move_counter;
+
+    let moves: Vec<u32> = movegen::generate_moves(&rebuilt)
+        .into_iter()
+        .filter(|&m| m != NULL_MOVE)
+        .collect();
+    let corners = rebuilt.live_corner_count(player);
+
+    (moves, corners)
+}
+
+#[test]
+pub fn counts_match_a_fresh_board_for_both_players() {
+    for seed in 0..6 {
+        let start_position = [
+            StartPosition::Corner,
+            StartPosition::Middle,
+            StartPosition::MiddleBlokee,
+        ][seed as usize % 3];
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut board = BoardState::new(start_position);
+
+        while !board.is_game_over() {
+            for player in [Player::White, Player::Black] {
+                let (moves, corners) = moves_from_scratch(&board, player);
+                assert_eq!(
+                    movegen::placements_for(&board, player),
+                    moves,
+                    "moves for {:?}\n{}",
+                    player,
+                    board
+                );
+                assert_eq!(board.legal_move_count(player), moves.len());
+                assert_eq!(board.live_corner_count(player), corners);
+            }
+
+            // the caches are already exact, so refiltering them changes nothing
+            let mut refiltered = board.clone();
+            movegen::update_move_cache_from_null_move(&mut refiltered);
+            for player in [Player::White, Player::Black] {
+                assert_eq!(refiltered.live_corners(player), board.live_corners(player));
+                assert_eq!(
+                    refiltered.legal_move_count(player),
+                    board.legal_move_count(player)
+                );
+            }
+
+            let m = *movegen::generate_moves(&board).choose(&mut rng).unwrap();
+            board.do_move(m);
+        }
+
+        assert_eq!(board.legal_move_count(Player::White), 0);
+        assert_eq!(board.live_corner_count(Player::Black), 0);
+    }
+}
+
+#[test]
+pub fn opening_mobility() {
+    let mut board = BoardState::new(StartPosition::Corner);
+    let first_moves = movegen::generate_moves(&board).len();
+    assert_eq!(movegen::generate_first_moves(&board).len(), first_moves);
+
+    assert_eq!(board.legal_move_count(Player::White), first_moves);
+    assert_eq!(board.legal_move_count(Player::Black), first_moves);
+    assert_eq!(board.live_corner_count(Player::White), 1);
+
+    // I1 in the corner leaves a single corner to play from
+    let i1 = movegen::generate_moves(&board)
+        .into_iter()
+        .find(|&m| movegen::Move::get_movetype(m) == 16)
+        .unwrap();
+    board.do_move(i1);
+    assert_eq!(board.live_corner_count(Player::White), 1);
+    assert_eq!(board.legal_move_count(Player::Black), first_moves);
+}
@@ -0,0 +1,145 @@
+use blok_rs::board::{BoardState, StartPosition};
+use blok_rs::movegen::{self, IllegalMove, Move, NULL_MOVE, check_move};
+
+// Every packed move with a legal encoding passes `check_move` exactly when the move generator
+// produces it.
+fn assert_matches_movegen(board: &BoardState) {
+    let legal_moves = movegen::generate_moves(board);
+
+    for m in 0..(1u32 << 17) {
+        assert_eq!(
+            check_move(board, m).is_ok(),
+            legal_moves.contains(&m),
+            "move {} ({}): {:?}\n{}",
+            m,
+            Move::to_notation(m),
+            check_move(board, m),
+            board
+        );
+    }
+}
+
+#[test]
+pub fn check_move_agrees_with_movegen() {
+    for start_position in [
+        StartPosition::Corner,
+        StartPosition::Middle,
+        StartPosition::MiddleBlokee,
+    ] {
+        let mut board = BoardState::new(start_position);
+        for ply in 0..12 {
+            if ply % 4 == 0 {
+                assert_matches_movegen(&board);
+            }
+            let m = *movegen::generate_moves(&board).iter().max().unwrap();
+            board.do_move(m);
+        }
+    }
+}
+
+#[test]
+pub fn check_move_reasons() {
+    let mut board = BoardState::new(StartPosition::Corner);
+    let at = |movetype, orientation, x, y, player| {
+        Move {
+            orientation,
+            y,
+            x,
+            movetype,
+            player,
+        }
+        .pack()
+    };
+
+    assert_eq!(
+        check_move(&board, at(16, 0, 0, 0, 1)),
+        Err(IllegalMove::WrongPlayer)
+    );
+    assert_eq!(
+        check_move(&board, at(25, 0, 0, 0, 0)),
+        Err(IllegalMove::InvalidPiece)
+    );
+    assert_eq!(
+        check_move(&board, at(7, 2, 0, 0, 0)),
+        Err(IllegalMove::InvalidOrientation)
+    );
+    assert_eq!(
+        check_move(&board, at(20, 0, 0, 12, 0)),
+        Err(IllegalMove::OutOfBounds)
+    );
+    assert_eq!(
+        check_move(&board, at(16, 0, 3, 3, 0)),
+        Err(IllegalMove::MissesStartSquare)
+    );
+    assert_eq!(
+        check_move(&board, NULL_MOVE),
+        Err(IllegalMove::PassWithMovesAvailable)
+    );
+
+    // A: I1 on a1, B: I1 on n14
+    board.do_move(at(16, 0, 0, 0, 0));
+    board.do_move(at(16, 0, 13, 13, 1));
+
+    assert_eq!(
+        check_move(&board, at(16, 0, 1, 1, 0)),
+        Err(IllegalMove::PieceAlreadyUsed)
+    );
+    assert_eq!(
+        check_move(&board, at(17, 0, 0, 0, 0)),
+        Err(IllegalMove::OverlapsOwnTile)
+    );
+    assert_eq!(
+        check_move(&board, at(17, 0, 0, 1, 0)),
+        Err(IllegalMove::TouchesOwnTile)
+    );
+    assert_eq!(
+        check_move(&board, at(17, 0, 5, 5, 0)),
+        Err(IllegalMove::NoCornerContact)
+    );
+    assert_eq!(check_move(&board, at(17, 0, 1, 1, 0)), Ok(()));
+
+    board.do_move(at(20, 1, 1, 1, 0));
+    assert_eq!(
+        check_move(&board, at(19, 1, 2, 2, 1)),
+        Err(IllegalMove::NoCornerContact)
+    );
+
+    assert_eq!(
+        check_move(&board, at(19, 1, 1, 1, 1)),
+        Err(IllegalMove::OverlapsOpponent)
+    );
+
+    board.null_move_counter = 2;
+    assert_eq!(
+        check_move(&board, at(17, 0, 12, 11, 1)),
+        Err(IllegalMove::GameOver)
+    );
+}
+
+#[test]
+pub fn untrusted_packed_moves() {
+    assert_eq!(
+        Move::try_from_packed(u32::MAX).unwrap_err(),
+        IllegalMove::InvalidEncoding
+    );
+    assert_eq!(
+        Move::try_from_packed(NULL_MOVE).unwrap_err(),
+        IllegalMove::InvalidPiece
+    );
+    // x = 15
+    assert_eq!(
+        Move::try_from_packed(15 << 7 | 16 << 11).unwrap_err(),
+        IllegalMove::OutOfBounds
+    );
+    assert_eq!(Move::try_from_packed(16 << 11).unwrap().movetype, 16);
+
+    let mut board = BoardState::new(StartPosition::Corner);
+    let before = board.to_notation();
+    for m in [u32::MAX, 1 << 20, 0x1ffff, 15 << 3 | 15 << 7] {
+        assert!(board.try_do_move(m).is_err());
+        assert_eq!(board.to_notation(), before);
+    }
+
+    assert_eq!(board.try_do_move(0), Ok(()));
+    assert_eq!(board.try_do_move(0), Err(IllegalMove::WrongPlayer));
+}
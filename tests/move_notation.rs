@@ -0,0 +1,91 @@
+use blok_rs::board::{BoardState, Player, StartPosition};
+use blok_rs::movegen::{self, IllegalMove, Move, NULL_MOVE};
+use blok_rs::notation::{MoveNotationError, PIECE_NAMES, piece_from_name};
+
+#[test]
+pub fn piece_names_are_unique() {
+    for (i, name) in PIECE_NAMES.iter().enumerate() {
+        assert_eq!(piece_from_name(name), Some(i as u8));
+    }
+    assert_eq!(piece_from_name("x5"), Some(7));
+    assert_eq!(piece_from_name("Q7"), None);
+}
+
+#[test]
+pub fn move_notation_format() {
+    let m = Move {
+        orientation: 3,
+        y: 6,
+        x: 4,
+        movetype: 0,
+        player: 1,
+    };
+
+    assert_eq!(Move::to_notation(m.pack()), "L5-3@e7");
+    assert_eq!(Move::to_notation(NULL_MOVE), "pass");
+    assert_eq!(
+        Move::parse_notation("L5-3@e7", Player::Black).unwrap(),
+        m.pack()
+    );
+    assert_eq!(
+        Move::parse_notation("pass", Player::White).unwrap(),
+        NULL_MOVE
+    );
+}
+
+#[test]
+pub fn move_notation_round_trip() {
+    let game_data = include_str!("./testdata/game_movedata.json");
+    let test_data: Vec<Vec<Vec<u32>>> = serde_json::from_str(game_data).unwrap();
+
+    for single_game_data in test_data.iter().take(3) {
+        let mut game = BoardState::new(StartPosition::Corner);
+
+        for move_data in single_game_data {
+            for m in movegen::generate_moves(&game) {
+                let notation = Move::to_notation(m);
+                assert_eq!(Move::from_notation(&notation, &game).unwrap(), m);
+            }
+
+            game.do_move(move_data[0]);
+        }
+    }
+}
+
+#[test]
+pub fn rejects_bad_move_notation() {
+    let board = BoardState::new(StartPosition::Corner);
+
+    assert_eq!(
+        Move::from_notation("L5@a1", &board).unwrap_err(),
+        MoveNotationError::Malformed("L5@a1".to_string())
+    );
+    assert_eq!(
+        Move::from_notation("Q5-0@a1", &board).unwrap_err(),
+        MoveNotationError::UnknownPiece("Q5".to_string())
+    );
+    assert_eq!(
+        Move::from_notation("X5-1@a1", &board).unwrap_err(),
+        MoveNotationError::InvalidOrientation {
+            piece: 7,
+            orientation: "1".to_string()
+        }
+    );
+    assert_eq!(
+        Move::from_notation("I1-0@o1", &board).unwrap_err(),
+        MoveNotationError::InvalidSquare("o1".to_string())
+    );
+    assert_eq!(
+        Move::from_notation("I1-0@a15", &board).unwrap_err(),
+        MoveNotationError::InvalidSquare("a15".to_string())
+    );
+    // well-formed, but doesn't cover the start square
+    assert_eq!(
+        Move::from_notation("I1-0@e5", &board).unwrap_err(),
+        MoveNotationError::Illegal {
+            notation: "I1-0@e5".to_string(),
+            reason: IllegalMove::MissesStartSquare
+        }
+    );
+    assert!(Move::from_notation("I1-0@a1", &board).is_ok());
+}
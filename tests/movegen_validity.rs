@@ -4,8 +4,6 @@ use blok_rs::board::Score;
 use blok_rs::board::StartPosition;
 use blok_rs::movegen;
 
-use serde_json;
-
 #[test]
 pub fn root_node_has_all_moves() {
     let mut game = BoardState::new(StartPosition::Corner);
@@ -89,7 +87,7 @@ pub fn game_length_movegen() {
             let mut expected_moves = move_data[1..].to_vec();
             expected_moves.sort();
 
-            assert_eq!(moves, expected_moves);
+            assert_eq!(moves, expected_moves, "\n{}", game);
         }
     }
 }
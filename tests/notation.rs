@@ -0,0 +1,96 @@
+use blok_rs::board::{BoardState, StartPosition};
+use blok_rs::movegen::{self, NULL_MOVE};
+use blok_rs::notation::NotationError;
+
+#[test]
+pub fn empty_board_notation() {
+    let board = BoardState::new(StartPosition::Corner);
+
+    assert_eq!(
+        board.to_notation(),
+        "14/14/14/14/14/14/14/14/14/14/14/14/14/14 0 0 a 0 corner"
+    );
+}
+
+#[test]
+pub fn notation_round_trip() {
+    let game_data = include_str!("./testdata/game_movedata.json");
+    let test_data: Vec<Vec<Vec<u32>>> = serde_json::from_str(game_data).unwrap();
+
+    for single_game_data in test_data.iter().take(5) {
+        let mut game = BoardState::new(StartPosition::Corner);
+
+        for move_data in single_game_data {
+            game.do_move(move_data[0]);
+
+            let notation = game.to_notation();
+            let parsed = BoardState::from_notation(&notation).unwrap();
+
+            assert_eq!(parsed.to_notation(), notation);
+            assert_eq!(parsed.player, game.player);
+            assert_eq!(parsed.null_move_counter, game.null_move_counter);
+            assert_eq!(parsed.score(), game.score());
+
+            if !game.is_game_over() {
+                let mut expected = movegen::generate_moves(&game);
+                let mut moves = movegen::generate_moves(&parsed);
+                expected.sort();
+                moves.sort();
+                assert_eq!(moves, expected);
+            }
+        }
+    }
+}
+
+#[test]
+pub fn notation_records_passes() {
+    let mut game = BoardState::new(StartPosition::Middle);
+    game.do_move(movegen::generate_moves(&game)[0]);
+    game.do_move(NULL_MOVE);
+
+    let notation = game.to_notation();
+    assert!(notation.ends_with(" 0 a 1 middle"));
+    assert_eq!(
+        BoardState::from_notation(&notation)
+            .unwrap()
+            .null_move_counter,
+        1
+    );
+}
+
+#[test]
+pub fn rejects_malformed_notation() {
+    let cases = [
+        ("14/14 0 0 a 0 corner", NotationError::RowCount(2)),
+        (
+            "14/14/14/14/14/14/14/14/14/14/14/14/14/14 0 0 a 0",
+            NotationError::FieldCount(5),
+        ),
+        (
+            "14/14/14/14/14/14/14/14/14/14/14/14/14/13 0 0 a 0 corner",
+            NotationError::InvalidRow(13),
+        ),
+        (
+            "14/14/14/14/14/14/14/14/14/14/14/14/14/14 0 0 c 0 corner",
+            NotationError::InvalidSideToMove("c".to_string()),
+        ),
+        (
+            "14/14/14/14/14/14/14/14/14/14/14/14/14/14 0 0 a 0 edge",
+            NotationError::InvalidStartPosition("edge".to_string()),
+        ),
+        (
+            "14/14/14/14/14/14/14/14/14/14/14/14/14/14 400000 0 a 0 corner",
+            NotationError::InvalidPieceMask("400000".to_string()),
+        ),
+    ];
+
+    for (notation, expected) in cases {
+        assert_eq!(BoardState::from_notation(notation).unwrap_err(), expected);
+    }
+
+    // a single tile that doesn't match the placed pieces
+    assert!(matches!(
+        BoardState::from_notation("a13/14/14/14/14/14/14/14/14/14/14/14/14/14 0 0 b 0 corner"),
+        Err(NotationError::Position(_))
+    ));
+}
@@ -0,0 +1,148 @@
+use blok_rs::bitboard::Bitboard;
+use blok_rs::board::{BoardState, Coord, Player, PositionError, StartPosition};
+use blok_rs::movegen::{self, Move, NULL_MOVE};
+
+fn sorted_moves(board: &BoardState) -> Vec<u32> {
+    let mut moves = movegen::generate_moves(board);
+    moves.sort();
+    moves
+}
+
+#[test]
+pub fn rebuilt_caches_match_incremental() {
+    let game_data = include_str!("./testdata/game_movedata.json");
+    let test_data: Vec<Vec<Vec<u32>>> = serde_json::from_str(game_data).unwrap();
+
+    for single_game_data in test_data.iter().take(5) {
+        let mut game = BoardState::new(StartPosition::Corner);
+        let mut placements: Vec<Move> = Vec::new();
+
+        for move_data in single_game_data {
+            game.do_move(move_data[0]);
+            if move_data[0] != NULL_MOVE {
+                placements.push(Move::unpack(move_data[0]));
+            }
+
+            if game.is_game_over() {
+                break;
+            }
+
+            let from_bitboards = BoardState::from_bitboards(
+                StartPosition::Corner,
+                game.player_a_bit_board,
+                game.player_b_bit_board,
+                game.player_a_remaining,
+                game.player_b_remaining,
+                game.player,
+            )
+            .unwrap();
+            assert_eq!(sorted_moves(&from_bitboards), sorted_moves(&game));
+
+            let from_placements =
+                BoardState::from_placements(StartPosition::Corner, &placements, game.player)
+                    .unwrap();
+            assert_eq!(from_placements.player_a_bit_board, game.player_a_bit_board);
+            assert_eq!(from_placements.player_b_bit_board, game.player_b_bit_board);
+            assert_eq!(sorted_moves(&from_placements), sorted_moves(&game));
+        }
+    }
+}
+
+#[test]
+pub fn empty_placements_match_new_board() {
+    let board = BoardState::from_placements(StartPosition::Middle, &[], Player::White).unwrap();
+
+    assert_eq!(
+        sorted_moves(&board),
+        sorted_moves(&BoardState::new(StartPosition::Middle))
+    );
+}
+
+#[test]
+pub fn rejects_invalid_placements() {
+    let monomino = |x, y, player| Move {
+        orientation: 0,
+        y,
+        x,
+        movetype: 16,
+        player,
+    };
+
+    let overlapping = [monomino(0, 0, 0), monomino(0, 0, 1)];
+    assert_eq!(
+        BoardState::from_placements(StartPosition::Corner, &overlapping, Player::White)
+            .unwrap_err(),
+        PositionError::Overlap(Coord { x: 0, y: 0 })
+    );
+
+    let duplicate = [monomino(0, 0, 0), monomino(1, 1, 0)];
+    assert_eq!(
+        BoardState::from_placements(StartPosition::Corner, &duplicate, Player::White).unwrap_err(),
+        PositionError::DuplicatePiece {
+            player: Player::White,
+            piece: 16
+        }
+    );
+
+    let domino = Move {
+        orientation: 0,
+        y: 1,
+        x: 0,
+        movetype: 17,
+        player: 0,
+    };
+    assert_eq!(
+        BoardState::from_placements(
+            StartPosition::Corner,
+            &[monomino(0, 0, 0), domino],
+            Player::White
+        )
+        .unwrap_err(),
+        PositionError::TouchesOwnTile {
+            player: Player::White,
+            piece: 17
+        }
+    );
+
+    let off_start = [monomino(5, 5, 0)];
+    assert_eq!(
+        BoardState::from_placements(StartPosition::Corner, &off_start, Player::Black).unwrap_err(),
+        PositionError::MissingStartSquare(Player::White)
+    );
+
+    let bad_orientation = [Move {
+        orientation: 3,
+        ..monomino(0, 0, 0)
+    }];
+    assert_eq!(
+        BoardState::from_placements(StartPosition::Corner, &bad_orientation, Player::Black)
+            .unwrap_err(),
+        PositionError::InvalidOrientation {
+            piece: 16,
+            orientation: 3
+        }
+    );
+
+    let off_board = [monomino(255, 0, 0)];
+    assert_eq!(
+        BoardState::from_placements(StartPosition::Corner, &off_board, Player::Black).unwrap_err(),
+        PositionError::OutOfBounds {
+            player: Player::White,
+            piece: 16
+        }
+    );
+
+    let bit_board = Bitboard::from_square(Coord { x: 0, y: 0 });
+    assert_eq!(
+        BoardState::from_bitboards(
+            StartPosition::Corner,
+            bit_board,
+            Bitboard::EMPTY,
+            0x1fffff,
+            0x1fffff,
+            Player::White
+        )
+        .unwrap_err(),
+        PositionError::TileCountMismatch(Player::White)
+    );
+}
@@ -0,0 +1,1151 @@
+use blok_rs::board::{BoardState, GameResult, Player, StartPosition};
+use blok_rs::mcts::SearchBudget;
+use blok_rs::movegen::{self, Move};
+use blok_rs::server::protocol::{ErrorCode, ForGame, PROTOCOL_VERSION, Request, Response};
+use blok_rs::server::{
+    GameLimits, GameStore, Reply, SearchPool, ServerConfig, Session, handle_websocket, serve_on,
+};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::json;
+use std::time::{Duration, Instant};
+use tokio_tungstenite::tungstenite::Message;
+
+fn init(session: &mut Session, position: Option<&str>) -> Vec<Response> {
+    session.handle_blocking(Request::Init {
+        start_pos: "corner".to_string(),
+        difficulty: "test".to_string(),
+        position: position.map(str::to_string),
+        ponder: false,
+        engine_side: None,
+    })
+}
+
+// A position from the middle of a game, where searches are quick
+fn midgame() -> String {
+    let mut board = BoardState::new(StartPosition::Corner);
+    for _ in 0..24 {
+        let m = *movegen::generate_moves(&board).iter().min().unwrap();
+        board.do_move(m);
+    }
+    board.to_notation()
+}
+
+fn handle_text(session: &mut Session, text: &str) -> Vec<Response> {
+    match session.handle_text(text) {
+        Reply::Responses(responses) => responses,
+        Reply::Search(job) => session.finish_search(job.run()),
+    }
+}
+
+fn error_code(responses: &[Response]) -> ErrorCode {
+    match responses {
+        [Response::Error { code, .. }] => *code,
+        other => panic!("Expected a single error, got {:?}", other),
+    }
+}
+
+#[test]
+pub fn messages_use_the_wire_format() {
+    let request: Request = serde_json::from_value(json!({"type": "findMove", "move": 5})).unwrap();
+    assert_eq!(request, Request::FindMove { mov: Some(5) });
+
+    let request: Request = serde_json::from_value(json!({
+        "type": "init",
+        "startPos": "middle",
+        "difficulty": "easy"
+    }))
+    .unwrap();
+    assert_eq!(request.name(), "init");
+
+    let response = Response::Move {
+        mov: 0,
+        notation: Move::to_notation(0),
+    };
+    assert_eq!(
+        serde_json::to_value(&response).unwrap(),
+        json!({"type": "move", "move": 0, "notation": "L5-0@a1"})
+    );
+
+    let request: Request =
+        serde_json::from_value(json!({"type": "setPosition", "startPos": "corner", "moves": []}))
+            .unwrap();
+    assert_eq!(request.name(), "setPosition");
+    let request: Request = serde_json::from_value(json!({"type": "undo"})).unwrap();
+    assert_eq!(request, Request::Undo { count: None });
+
+    let state = serde_json::to_value(Session::new().handle_blocking(Request::GetState)).unwrap();
+    assert_eq!(state[0]["startPos"], "corner");
+    assert_eq!(state[0]["toMove"], "white");
+    assert!(state[0].get("initialPosition").is_none());
+
+    let response = Response::GameOver {
+        result: GameResult::Draw,
+        score: BoardState::new(StartPosition::Corner).score(),
+    };
+    assert_eq!(
+        serde_json::to_value(&response).unwrap(),
+        json!({"type": "gameOver", "result": "draw", "score": {"playerA": 0, "playerB": 0}})
+    );
+}
+
+#[test]
+pub fn handshake_negotiates_a_version() {
+    let mut session = Session::new();
+
+    match handle_text(
+        &mut session,
+        r#"{"type": "hello", "protocolVersions": [0, 1, 99]}"#,
+    )
+    .as_slice()
+    {
+        [
+            Response::Hello {
+                protocol_version,
+                capabilities,
+                ..
+            },
+        ] => {
+            assert_eq!(*protocol_version, PROTOCOL_VERSION);
+            assert!(capabilities.iter().any(|c| c == "findMove"));
+        }
+        other => panic!("Expected hello, got {:?}", other),
+    }
+
+    let responses = session.handle_blocking(Request::Hello {
+        protocol_versions: vec![99],
+    });
+    assert_eq!(error_code(&responses), ErrorCode::UnsupportedVersion);
+}
+
+#[test]
+pub fn every_request_gets_a_reply() {
+    let mut session = Session::new();
+
+    assert_eq!(
+        error_code(&handle_text(&mut session, "not json")),
+        ErrorCode::InvalidRequest
+    );
+    assert_eq!(
+        error_code(&handle_text(&mut session, r#"{"type": "resign"}"#)),
+        ErrorCode::InvalidRequest
+    );
+
+    assert_eq!(
+        init(&mut session, None),
+        vec![Response::Ack {
+            request: "init".to_string()
+        }]
+    );
+    assert_eq!(
+        error_code(&session.handle_blocking(Request::Init {
+            start_pos: "edge".to_string(),
+            difficulty: "test".to_string(),
+            position: None,
+            ponder: false,
+            engine_side: None,
+        })),
+        ErrorCode::InvalidStartPosition
+    );
+    assert_eq!(
+        error_code(&init(&mut session, Some("garbage"))),
+        ErrorCode::InvalidPosition
+    );
+
+    // a move in the wrong corner
+    let responses = session.handle_blocking(Request::FindMove {
+        mov: Some(Move::parse_notation("I1-0@n14", Player::White).unwrap()),
+    });
+    match responses.as_slice() {
+        [
+            Response::Error {
+                code: ErrorCode::IllegalMove,
+                mov: Some(_),
+                request: Some(request),
+                ..
+            },
+        ] => assert_eq!(request, "findMove"),
+        other => panic!("Expected an illegal move error, got {:?}", other),
+    }
+    assert_eq!(session.game().ply(), 0);
+
+    init(&mut session, Some(&midgame()));
+    match session
+        .handle_blocking(Request::FindMove { mov: None })
+        .as_slice()
+    {
+        [Response::Move { mov, notation }] => {
+            assert_eq!(*notation, Move::to_notation(*mov));
+            assert_eq!(session.game().moves(), [*mov]);
+        }
+        other => panic!("Expected a move, got {:?}", other),
+    }
+}
+
+#[test]
+pub fn finished_games_report_game_over() {
+    // play out a game with the smallest moves, stopping one ply from the end
+    let mut board = BoardState::new(StartPosition::Corner);
+    let mut moves = Vec::new();
+    while !board.is_game_over() {
+        let m = *movegen::generate_moves(&board).iter().min().unwrap();
+        board.do_move(m);
+        moves.push(m);
+    }
+    let last = moves.pop().unwrap();
+    let mut before_last = BoardState::new(StartPosition::Corner);
+    for &m in &moves {
+        before_last.do_move(m);
+    }
+
+    let mut session = Session::new();
+    init(&mut session, Some(&before_last.to_notation()));
+    match session
+        .handle_blocking(Request::FindMove { mov: Some(last) })
+        .as_slice()
+    {
+        [Response::GameOver { result, score }] => {
+            assert_eq!(*result, board.game_result());
+            assert_eq!(*score, board.score());
+        }
+        other => panic!("Expected game over, got {:?}", other),
+    }
+
+    let responses = session.handle_blocking(Request::FindMove { mov: None });
+    assert!(matches!(responses.as_slice(), [Response::GameOver { .. }]));
+}
+
+#[test]
+pub fn only_one_search_runs_at_a_time() {
+    let mut session = Session::new();
+    init(&mut session, Some(&midgame()));
+
+    let Reply::Search(job) = session.handle(Request::FindMove { mov: None }) else {
+        panic!("Expected a search");
+    };
+    assert!(session.is_searching());
+
+    let Reply::Responses(responses) = session.handle(Request::FindMove { mov: None }) else {
+        panic!("Expected a reply");
+    };
+    assert_eq!(error_code(&responses), ErrorCode::Busy);
+    let Reply::Responses(responses) = session.handle(Request::Hello {
+        protocol_versions: vec![PROTOCOL_VERSION],
+    }) else {
+        panic!("Expected a reply");
+    };
+    assert!(matches!(responses.as_slice(), [Response::Hello { .. }]));
+
+    let responses = session.finish_search(job.run());
+    assert!(matches!(responses.as_slice(), [Response::Move { .. }]));
+    assert!(!session.is_searching());
+    assert_eq!(session.game().ply(), 1);
+}
+
+#[test]
+pub fn stop_plays_the_best_move_so_far() {
+    let mut session = Session::new();
+    assert_eq!(
+        session.handle_blocking(Request::Stop),
+        vec![Response::Ack {
+            request: "stop".to_string()
+        }]
+    );
+
+    // a long search from the empty board, stopped before it gets going
+    session.handle_blocking(Request::Init {
+        start_pos: "corner".to_string(),
+        difficulty: "hard".to_string(),
+        position: None,
+        ponder: false,
+        engine_side: None,
+    });
+    let Reply::Search(job) = session.handle(Request::FindMove { mov: None }) else {
+        panic!("Expected a search");
+    };
+    let Reply::Responses(responses) = session.handle(Request::Stop) else {
+        panic!("Expected a reply");
+    };
+    assert!(responses.is_empty());
+
+    let responses = session.finish_search(job.run());
+    assert!(matches!(responses.as_slice(), [Response::Move { .. }]));
+    assert_eq!(session.game().ply(), 1);
+}
+
+#[test]
+pub fn init_abandons_the_search() {
+    let mut session = Session::new();
+    init(&mut session, Some(&midgame()));
+
+    let Reply::Search(abandoned) = session.handle(Request::FindMove { mov: None }) else {
+        panic!("Expected a search");
+    };
+    assert_eq!(
+        init(&mut session, Some(&midgame())),
+        vec![Response::Ack {
+            request: "init".to_string()
+        }]
+    );
+    assert!(!session.is_searching());
+
+    // the new game can be searched before the abandoned search has finished
+    let Reply::Search(job) = session.handle(Request::FindMove { mov: None }) else {
+        panic!("Expected a search");
+    };
+    assert!(session.finish_search(abandoned.run()).is_empty());
+    assert!(session.is_searching());
+    assert_eq!(session.game().ply(), 0);
+
+    let responses = session.finish_search(job.run());
+    assert!(matches!(responses.as_slice(), [Response::Move { .. }]));
+    assert_eq!(session.game().ply(), 1);
+}
+
+#[test]
+pub fn pondering_reuses_the_tree() {
+    let mut session = Session::new();
+    session.handle_blocking(Request::Init {
+        start_pos: "corner".to_string(),
+        difficulty: "test".to_string(),
+        position: Some(midgame()),
+        ponder: true,
+        engine_side: None,
+    });
+
+    assert!(matches!(
+        session
+            .handle_blocking(Request::FindMove { mov: None })
+            .as_slice(),
+        [Response::Move { .. }]
+    ));
+    let ponder = session.next_search().expect("Expected to ponder");
+    assert!(session.is_pondering());
+    assert!(!session.is_searching());
+
+    // let the ponder search finish before the client moves, then play the reply it searched most
+    let pondered = ponder.run();
+    let client_move = pondered.best_move().unwrap();
+    let Reply::Responses(responses) = session.handle(Request::FindMove {
+        mov: Some(client_move),
+    }) else {
+        panic!("Expected the move to wait for the ponder search");
+    };
+    assert!(responses.is_empty());
+    assert!(session.is_searching());
+
+    assert!(session.finish_search(pondered).is_empty());
+    let job = session.next_search().expect("Expected the queued move");
+    let responses = session.finish_search(job.run());
+    assert!(matches!(responses.as_slice(), [Response::Move { .. }]));
+
+    let stats = session
+        .game()
+        .record()
+        .move_info
+        .last()
+        .unwrap()
+        .search
+        .unwrap();
+    assert!(stats.plays > 1_000, "{} playouts", stats.plays);
+}
+
+#[test]
+pub fn engine_plays_its_side() {
+    let position = midgame();
+    assert_eq!(
+        BoardState::from_notation(&position).unwrap().player,
+        Player::White
+    );
+    let init_as = |session: &mut Session, side| {
+        session.handle_blocking(Request::Init {
+            start_pos: "corner".to_string(),
+            difficulty: "test".to_string(),
+            position: Some(position.clone()),
+            ponder: false,
+            engine_side: Some(side),
+        })
+    };
+
+    // the engine moves first as white, and won't take a move for its own side
+    let mut session = Session::new();
+    init_as(&mut session, Player::White);
+    let client_move = movegen::generate_moves(session.game().board())[0];
+    assert_eq!(
+        error_code(&session.handle_blocking(Request::FindMove {
+            mov: Some(client_move)
+        })),
+        ErrorCode::WrongTurn
+    );
+    assert!(matches!(
+        session
+            .handle_blocking(Request::FindMove { mov: None })
+            .as_slice(),
+        [Response::Move { .. }]
+    ));
+    assert_eq!(session.game().ply(), 1);
+
+    // as black, it waits for the client's move
+    init_as(&mut session, Player::Black);
+    assert_eq!(session.game().ply(), 0);
+    assert_eq!(
+        error_code(&session.handle_blocking(Request::FindMove { mov: None })),
+        ErrorCode::WrongTurn
+    );
+    assert!(matches!(
+        session
+            .handle_blocking(Request::FindMove {
+                mov: Some(client_move)
+            })
+            .as_slice(),
+        [Response::Move { .. }]
+    ));
+    assert_eq!(session.game().ply(), 2);
+
+    let state = serde_json::to_value(session.handle_blocking(Request::GetState)).unwrap();
+    assert_eq!(state[0]["engineSide"], "black");
+}
+
+#[test]
+pub fn analyze_streams_the_best_moves() {
+    let mut session = Session::new();
+    init(&mut session, Some(&midgame()));
+
+    let Reply::Search(job) = session.handle(Request::Analyze {
+        top: Some(2),
+        difficulty: None,
+    }) else {
+        panic!("Expected a search");
+    };
+    assert_eq!(
+        error_code(&session.handle_blocking(Request::FindMove { mov: None })),
+        ErrorCode::Busy
+    );
+
+    let mut updates = Vec::new();
+    let outcome = job.run_with_updates(|update| updates.push(update));
+    let mut reports: Vec<Response> = updates
+        .into_iter()
+        .map(|update| session.accept_update(update).unwrap())
+        .collect();
+    reports.extend(session.finish_search(outcome));
+    assert_eq!(reports.len(), 4);
+
+    let mut last_iterations = 0;
+    for (i, report) in reports.iter().enumerate() {
+        let Response::Analysis {
+            is_final,
+            iterations,
+            moves,
+        } = report
+        else {
+            panic!("Expected an analysis, got {:?}", report);
+        };
+        assert_eq!(*is_final, i == 3);
+        assert!(*iterations > last_iterations);
+        last_iterations = *iterations;
+
+        assert_eq!(moves.len(), 2);
+        assert!(moves[0].visits >= moves[1].visits);
+        for m in moves {
+            assert_eq!(m.pv[0], m.mov);
+            assert_eq!(m.notation, Move::to_notation(m.mov));
+            assert!((0.0..=1.0).contains(&m.win_rate));
+        }
+    }
+    assert_eq!(last_iterations, 1_000);
+
+    // nothing was played
+    assert_eq!(session.game().ply(), 0);
+    assert!(!session.is_searching());
+}
+
+#[test]
+pub fn evaluate_reports_a_win_probability() {
+    let mut session = Session::new();
+    let Reply::Search(job) = session.handle(Request::Evaluate {
+        position: Some(midgame()),
+        difficulty: Some("test".to_string()),
+    }) else {
+        panic!("Expected a search");
+    };
+
+    // abandoned searches' updates are dropped
+    let mut updates = Vec::new();
+    let outcome = job.run_with_updates(|update| updates.push(update));
+    init(&mut session, None);
+    assert!(
+        updates
+            .into_iter()
+            .all(|update| session.accept_update(update).is_none())
+    );
+    assert!(session.finish_search(outcome).is_empty());
+
+    let responses = session.handle_blocking(Request::Evaluate {
+        position: Some(midgame()),
+        difficulty: Some("test".to_string()),
+    });
+    match responses.as_slice() {
+        [
+            Response::Evaluation {
+                is_final: true,
+                player: Player::White,
+                win_probability,
+                ..
+            },
+        ] => assert!((0.0..=1.0).contains(win_probability)),
+        other => panic!("Expected an evaluation, got {:?}", other),
+    }
+
+    // finished games aren't searched
+    let mut board = BoardState::new(StartPosition::Corner);
+    while !board.is_game_over() {
+        board.do_move(*movegen::generate_moves(&board).iter().min().unwrap());
+    }
+    let responses = session.handle_blocking(Request::Evaluate {
+        position: Some(board.to_notation()),
+        difficulty: None,
+    });
+    let expected = match (board.game_result(), board.player) {
+        (GameResult::Draw, _) => 0.5,
+        (GameResult::PlayerAWon, Player::White) | (GameResult::PlayerBWon, Player::Black) => 1.,
+        _ => 0.,
+    };
+    assert_eq!(
+        serde_json::to_value(&responses[0]).unwrap(),
+        json!({
+            "type": "evaluation",
+            "final": true,
+            "iterations": 0,
+            "toMove": if board.player == Player::White { "white" } else { "black" },
+            "winProbability": expected
+        })
+    );
+}
+
+fn state(responses: &[Response]) -> (Vec<u32>, String, Player, GameResult) {
+    match responses {
+        [
+            Response::State {
+                moves,
+                position,
+                player,
+                result,
+                ..
+            },
+        ] => (moves.clone(), position.clone(), *player, *result),
+        other => panic!("Expected the state, got {:?}", other),
+    }
+}
+
+#[test]
+pub fn set_position_replays_the_moves() {
+    let mut board = BoardState::new(StartPosition::Middle);
+    let mut moves = Vec::new();
+    for _ in 0..6 {
+        let m = *movegen::generate_moves(&board).iter().max().unwrap();
+        board.do_move(m);
+        moves.push(m);
+    }
+
+    let mut session = Session::new();
+    let responses = session.handle_blocking(Request::SetPosition {
+        start_pos: "middle".to_string(),
+        position: None,
+        moves: moves.clone(),
+    });
+    assert_eq!(
+        state(&responses),
+        (
+            moves.clone(),
+            board.to_notation(),
+            board.player,
+            GameResult::InProgress
+        )
+    );
+    assert_eq!(session.handle_blocking(Request::GetState), responses);
+
+    // a bad move anywhere in the list leaves the game alone
+    let mut bad_moves = moves.clone();
+    bad_moves[3] = bad_moves[1];
+    match session
+        .handle_blocking(Request::SetPosition {
+            start_pos: "middle".to_string(),
+            position: None,
+            moves: bad_moves,
+        })
+        .as_slice()
+    {
+        [
+            Response::Error {
+                code: ErrorCode::IllegalMove,
+                mov: Some(m),
+                ..
+            },
+        ] => assert_eq!(*m, moves[1]),
+        other => panic!("Expected an illegal move error, got {:?}", other),
+    }
+    assert_eq!(session.game().moves(), moves);
+}
+
+#[test]
+pub fn undo_takes_back_moves() {
+    let mut session = Session::new();
+    init(&mut session, Some(&midgame()));
+    let before = state(&session.handle_blocking(Request::GetState));
+
+    let client_move = movegen::generate_moves(session.game().board())[0];
+    session.handle_blocking(Request::FindMove {
+        mov: Some(client_move),
+    });
+    assert_eq!(session.game().ply(), 2);
+
+    let after_undo = state(&session.handle_blocking(Request::Undo { count: Some(2) }));
+    assert_eq!(after_undo, before);
+
+    assert_eq!(
+        error_code(&session.handle_blocking(Request::Undo { count: None })),
+        ErrorCode::InvalidRequest
+    );
+}
+
+#[test]
+pub fn resyncing_abandons_the_search() {
+    let mut session = Session::new();
+    init(&mut session, Some(&midgame()));
+    let client_move = movegen::generate_moves(session.game().board())[0];
+
+    let Reply::Search(job) = session.handle(Request::FindMove {
+        mov: Some(client_move),
+    }) else {
+        panic!("Expected a search");
+    };
+    let (moves, ..) = state(&session.handle_blocking(Request::Undo { count: None }));
+    assert!(moves.is_empty());
+    assert!(!session.is_searching());
+
+    assert!(session.finish_search(job.run()).is_empty());
+    assert_eq!(session.game().ply(), 0);
+}
+
+#[test]
+pub fn messages_carry_their_game() {
+    let request: ForGame<Request> =
+        serde_json::from_value(json!({"type": "findMove", "game": "12ab", "move": 5})).unwrap();
+    assert_eq!(
+        request,
+        ForGame::new("12ab", Request::FindMove { mov: Some(5) })
+    );
+
+    let request: ForGame<Request> = serde_json::from_value(json!({"type": "stop"})).unwrap();
+    assert_eq!(request, ForGame::untagged(Request::Stop));
+
+    let response = ForGame::new(
+        "12ab",
+        Response::Ack {
+            request: "newGame".to_string(),
+        },
+    );
+    assert_eq!(
+        serde_json::to_value(&response).unwrap(),
+        json!({"type": "ack", "game": "12ab", "request": "newGame"})
+    );
+}
+
+#[test]
+pub fn suspended_sessions_keep_the_tree() {
+    let mut session = Session::new();
+    session.handle_blocking(Request::Init {
+        start_pos: "corner".to_string(),
+        difficulty: "test".to_string(),
+        position: Some(midgame()),
+        ponder: true,
+        engine_side: None,
+    });
+    session.handle_blocking(Request::FindMove { mov: None });
+    let pondered = session.next_search().expect("Expected to ponder").run();
+    let client_move = pondered.best_move().unwrap();
+
+    // the client leaves while the engine ponders
+    session.suspend();
+    assert!(!session.is_pondering());
+    assert!(session.finish_search(pondered).is_empty());
+    assert!(session.next_search().is_none());
+
+    let responses = session.handle_blocking(Request::FindMove {
+        mov: Some(client_move),
+    });
+    assert!(matches!(responses.as_slice(), [Response::Move { .. }]));
+    let stats = session
+        .game()
+        .record()
+        .move_info
+        .last()
+        .unwrap()
+        .search
+        .unwrap();
+    assert!(stats.plays > 1_000, "{} playouts", stats.plays);
+}
+
+#[test]
+pub fn unused_games_expire() {
+    let limits = GameLimits {
+        max_games: 2,
+        max_per_connection: 2,
+    };
+    let games = GameStore::new(Default::default(), Duration::from_secs(60), limits);
+    let later = |secs| Instant::now() + Duration::from_secs(secs);
+    let (id, mut session) = games.create().unwrap();
+    assert_eq!(games.resume(&id).err(), Some(ErrorCode::GameInUse));
+    assert_eq!(games.resume("nope").err(), Some(ErrorCode::UnknownGame));
+
+    init(&mut session, Some(&midgame()));
+    games.leave(&id, session);
+    let session = games.resume(&id).unwrap();
+    assert_eq!(session.game().board().to_notation(), midgame());
+
+    // a left game can be resumed until the timeout after it was left
+    assert!(games.touch(&id));
+    games.leave(&id, session);
+    assert_eq!(games.expire(later(59)), 0);
+    assert_eq!(games.expire(later(61)), 1);
+    assert!(games.is_empty());
+    assert_eq!(games.resume(&id).err(), Some(ErrorCode::UnknownGame));
+
+    // so can an open game nobody uses, e.g. if its connection went away without leaving it
+    let (id, session) = games.create().unwrap();
+    assert_eq!(games.expire(later(61)), 1);
+    assert!(!games.touch(&id));
+    // leaving it doesn't bring it back
+    games.leave(&id, session);
+    assert!(games.is_empty());
+}
+
+#[test]
+pub fn the_store_refuses_games_beyond_its_limit() {
+    let limits = GameLimits {
+        max_games: 2,
+        max_per_connection: 2,
+    };
+    let games = GameStore::new(Default::default(), Duration::from_secs(60), limits);
+    let (first, _) = games.create().unwrap();
+    let (second, session) = games.create().unwrap();
+    assert_eq!(games.create().err(), Some(ErrorCode::TooManyGames));
+
+    // left games count until they're gone
+    games.leave(&second, session);
+    assert_eq!(games.create().err(), Some(ErrorCode::TooManyGames));
+    games.remove(&first);
+    assert!(games.create().is_ok());
+}
+
+#[test]
+pub fn configured_budgets_replace_the_built_in_ones() {
+    let budgets = [
+        ("hard".to_string(), SearchBudget::Iterations(80)),
+        ("blitz".to_string(), SearchBudget::Millis(50)),
+    ];
+    let mut session = Session::with_budgets(budgets.into_iter().collect());
+
+    for difficulty in ["hard", "blitz"] {
+        let responses = session.handle_blocking(Request::Evaluate {
+            position: Some(midgame()),
+            difficulty: Some(difficulty.to_string()),
+        });
+        match responses.as_slice() {
+            [Response::Evaluation { iterations, .. }] => {
+                assert!(*iterations > 0 && *iterations < 100_000)
+            }
+            other => panic!("Expected an evaluation, got {:?}", other),
+        }
+    }
+}
+
+type ClientSocket =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+// Serve on a free port, returning the address to connect to
+async fn spawn_server(config: ServerConfig) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(serve_on(listener, config));
+    addr
+}
+
+async fn connect(addr: &str) -> ClientSocket {
+    tokio_tungstenite::connect_async(addr).await.unwrap().0
+}
+
+async fn send(ws: &mut ClientSocket, request: serde_json::Value) {
+    ws.send(Message::Text(request.to_string())).await.unwrap();
+}
+
+// The next message from the server, which must be a reply
+async fn next_reply(ws: &mut ClientSocket) -> serde_json::Value {
+    let Some(Ok(Message::Text(text))) = ws.next().await else {
+        panic!("Connection closed early");
+    };
+    serde_json::from_str(&text).unwrap()
+}
+
+async fn next_response(ws: &mut ClientSocket) -> Response {
+    serde_json::from_value(next_reply(ws).await).unwrap()
+}
+
+// Send a request and wait for its first reply, skipping search progress
+async fn ws_request(ws: &mut ClientSocket, request: serde_json::Value) -> serde_json::Value {
+    send(ws, request).await;
+    loop {
+        let reply = next_reply(ws).await;
+        if reply["type"] != "info" {
+            return reply;
+        }
+    }
+}
+
+// A server with a single search running at a time, as several tests need searches to queue
+async fn spawn_single_search_server() -> String {
+    spawn_server(ServerConfig {
+        max_concurrent_searches: 1,
+        ..Default::default()
+    })
+    .await
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+pub async fn websocket_stays_responsive_during_a_search() {
+    let mut ws = connect(&spawn_single_search_server().await).await;
+    let requests = [
+        json!({"type": "init", "startPos": "corner", "difficulty": "test", "position": midgame()}),
+        json!({"type": "findMove"}),
+        json!({"type": "hello", "protocolVersions": [PROTOCOL_VERSION]}),
+    ];
+    for request in requests {
+        send(&mut ws, request).await;
+    }
+
+    let mut reply_types = Vec::new();
+    while reply_types.len() < 3 {
+        reply_types.push(match next_response(&mut ws).await {
+            Response::Info { .. } => continue,
+            Response::Ack { .. } => "ack",
+            Response::Hello { .. } => "hello",
+            Response::Move { .. } => "move",
+            other => panic!("Unexpected response {:?}", other),
+        });
+    }
+
+    // the handshake is answered while the engine is still thinking
+    assert_eq!(reply_types, ["ack", "hello", "move"]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+pub async fn websocket_stop_ends_the_search() {
+    let mut ws = connect(&spawn_single_search_server().await).await;
+    let requests = [
+        json!({"type": "init", "startPos": "corner", "difficulty": "hard"}),
+        json!({"type": "findMove"}),
+        json!({"type": "stop"}),
+    ];
+    for request in requests {
+        send(&mut ws, request).await;
+    }
+
+    let mut replies = Vec::new();
+    while replies.len() < 2 {
+        match next_response(&mut ws).await {
+            Response::Info { .. } => {}
+            response => replies.push(response),
+        }
+    }
+
+    assert!(matches!(replies[0], Response::Ack { .. }));
+    assert!(matches!(replies[1], Response::Move { .. }));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+pub async fn websocket_reports_search_progress() {
+    let mut ws = connect(&spawn_single_search_server().await).await;
+    let requests = [
+        json!({"type": "init", "startPos": "corner", "difficulty": "hard", "position": midgame()}),
+        json!({"type": "findMove"}),
+    ];
+    for request in requests {
+        send(&mut ws, request).await;
+    }
+
+    let mut responses = Vec::new();
+    loop {
+        let response = next_response(&mut ws).await;
+        let is_info = matches!(response, Response::Info { .. });
+        responses.push(response);
+
+        // the search is far from done after the first report
+        if is_info {
+            send(&mut ws, json!({"type": "stop"})).await;
+        }
+        if matches!(responses.last(), Some(Response::Move { .. })) {
+            break;
+        }
+    }
+
+    assert!(matches!(responses[0], Response::Ack { .. }));
+    let Response::Info {
+        iterations,
+        best_move,
+        ref notation,
+        visits,
+        win_rate,
+        ref pv,
+        ..
+    } = responses[1]
+    else {
+        panic!("Expected info, got {:?}", responses[1]);
+    };
+    assert!(iterations > 0 && iterations < 100_000);
+    assert_eq!(*notation, Move::to_notation(best_move));
+    assert!(visits <= iterations);
+    assert!((0.0..=1.0).contains(&win_rate));
+    assert_eq!(pv[0], best_move);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+pub async fn websocket_turns_away_sessions_beyond_the_limit() {
+    let addr = spawn_server(ServerConfig {
+        max_concurrent_searches: 1,
+        max_sessions: Some(1),
+        ..Default::default()
+    })
+    .await;
+
+    let mut first = connect(&addr).await;
+    // a reply means the first connection has its session
+    send(&mut first, json!({"type": "getState"})).await;
+    next_reply(&mut first).await;
+
+    let mut second = connect(&addr).await;
+    let response = next_response(&mut second).await;
+    assert_eq!(error_code(&[response]), ErrorCode::ServerFull);
+    assert!(matches!(
+        second.next().await,
+        Some(Ok(Message::Close(_))) | None
+    ));
+
+    // the session is freed once the first connection has closed, which the server finishes
+    // just after closing the socket, so a connection right away may still be turned away
+    first.close(None).await.unwrap();
+    while first.next().await.is_some() {}
+    let reply = loop {
+        let mut ws = connect(&addr).await;
+        let reply = ws_request(&mut ws, json!({"type": "getState"})).await;
+        if reply["code"] != "serverFull" {
+            break reply;
+        }
+    };
+    assert_eq!(reply["type"], "state");
+}
+
+// Servers that hand their connections to `handle_websocket` on tasks the tests can wait for, to
+// know when the server is done with a connection and has left its games. Searches run one at a
+// time, and the `forever` difficulty searches until it's stopped.
+struct TestServer {
+    listener: tokio::net::TcpListener,
+    pool: SearchPool,
+    games: GameStore,
+}
+
+impl TestServer {
+    async fn new() -> Self {
+        let limits = GameLimits {
+            max_games: 16,
+            max_per_connection: 4,
+        };
+        let budgets = [("forever".to_string(), SearchBudget::Millis(3_600_000))].into();
+        Self {
+            listener: tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap(),
+            pool: SearchPool::new(1),
+            games: GameStore::new(budgets, Duration::from_secs(600), limits),
+        }
+    }
+
+    async fn connect(&self) -> (ClientSocket, tokio::task::JoinHandle<()>) {
+        let addr = self.listener.local_addr().unwrap();
+        let (client, server) = tokio::join!(
+            tokio_tungstenite::connect_async(format!("ws://{}", addr)),
+            async {
+                let (stream, _) = self.listener.accept().await.unwrap();
+                tokio_tungstenite::accept_async(stream).await.unwrap()
+            }
+        );
+        let connection = tokio::spawn(handle_websocket(
+            server,
+            self.pool.clone(),
+            self.games.clone(),
+        ));
+
+        (client.unwrap().0, connection)
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+pub async fn websocket_games_survive_reconnecting() {
+    let server = TestServer::new().await;
+
+    // one connection playing two games
+    let (mut ws, connection) = server.connect().await;
+    let reply = ws_request(
+        &mut ws,
+        json!({"type": "init", "startPos": "corner", "difficulty": "test", "position": midgame()}),
+    )
+    .await;
+    assert_eq!(reply["type"], "ack");
+    let first = reply["game"].as_str().unwrap().to_string();
+
+    let reply = ws_request(&mut ws, json!({"type": "newGame"})).await;
+    assert_eq!(reply["request"], "newGame");
+    let second = reply["game"].as_str().unwrap().to_string();
+    assert_ne!(first, second);
+
+    let reply = ws_request(
+        &mut ws,
+        json!({"type": "setPosition", "game": second, "startPos": "middle", "moves": []}),
+    )
+    .await;
+    assert_eq!(
+        (&reply["type"], &reply["game"]),
+        (&json!("state"), &json!(second))
+    );
+    let reply = ws_request(&mut ws, json!({"type": "findMove"})).await;
+    assert_eq!(
+        (&reply["type"], &reply["game"]),
+        (&json!("move"), &json!(first))
+    );
+    let reply = ws_request(&mut ws, json!({"type": "getState", "game": first})).await;
+    let moves = reply["moves"].clone();
+
+    // a game is only open on one connection at a time
+    let (mut ws_2, _) = server.connect().await;
+    let reply = ws_request(&mut ws_2, json!({"type": "resume", "game": first})).await;
+    assert_eq!(reply["code"], "gameInUse");
+
+    // the games are kept for the next connection once the server is done with this one, and
+    // only once they're asked for
+    drop(ws);
+    connection.await.unwrap();
+    let (mut ws, _) = server.connect().await;
+    let reply = ws_request(&mut ws, json!({"type": "getState", "game": first})).await;
+    assert_eq!(reply["code"], "unknownGame");
+    let reply = ws_request(&mut ws, json!({"type": "resume", "game": first})).await;
+    assert_eq!((&reply["type"], &reply["moves"]), (&json!("state"), &moves));
+    // requests without an ID carry on with the resumed game
+    let reply = ws_request(&mut ws, json!({"type": "findMove"})).await;
+    assert_eq!(
+        (&reply["type"], &reply["game"]),
+        (&json!("move"), &json!(first))
+    );
+
+    let reply = ws_request(&mut ws_2, json!({"type": "resume", "game": second})).await;
+    assert_eq!(reply["startPos"], "middle");
+    assert_eq!(reply["moves"], json!([]));
+    let reply = ws_request(&mut ws_2, json!({"type": "closeGame", "game": second})).await;
+    assert_eq!(reply["type"], "ack");
+    let reply = ws_request(&mut ws, json!({"type": "resume", "game": second})).await;
+    assert_eq!(reply["code"], "unknownGame");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+pub async fn aborted_connections_leave_their_games() {
+    let server = TestServer::new().await;
+
+    let (mut ws, connection) = server.connect().await;
+    let reply = ws_request(
+        &mut ws,
+        json!({"type": "init", "startPos": "corner", "difficulty": "test", "position": midgame()}),
+    )
+    .await;
+    let id = reply["game"].clone();
+
+    // the connection's task ends without closing it
+    connection.abort();
+    assert!(connection.await.unwrap_err().is_cancelled());
+
+    let (mut ws, _) = server.connect().await;
+    let reply = ws_request(&mut ws, json!({"type": "resume", "game": id})).await;
+    assert_eq!(
+        (&reply["type"], &reply["position"]),
+        (&json!("state"), &json!(midgame()))
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+pub async fn websocket_limits_the_games_on_a_connection() {
+    let mut ws = connect(
+        &spawn_server(ServerConfig {
+            max_concurrent_searches: 1,
+            max_games_per_connection: 2,
+            ..Default::default()
+        })
+        .await,
+    )
+    .await;
+
+    let reply = ws_request(&mut ws, json!({"type": "getState"})).await;
+    assert_eq!(reply["type"], "state");
+    let reply = ws_request(&mut ws, json!({"type": "newGame"})).await;
+    assert_eq!(reply["type"], "ack");
+    let second = reply["game"].clone();
+
+    let reply = ws_request(&mut ws, json!({"type": "newGame"})).await;
+    assert_eq!(reply["code"], "tooManyGames");
+
+    // closing a game makes room for another
+    let reply = ws_request(&mut ws, json!({"type": "closeGame", "game": second})).await;
+    assert_eq!(reply["type"], "ack");
+    let reply = ws_request(&mut ws, json!({"type": "newGame"})).await;
+    assert_eq!(reply["type"], "ack");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+pub async fn closing_takes_back_searches_waiting_their_turn() {
+    let server = TestServer::new().await;
+
+    // a search that holds the pool until it's stopped
+    let (mut busy, _) = server.connect().await;
+    let reply = ws_request(
+        &mut busy,
+        json!({"type": "init", "startPos": "corner", "difficulty": "forever", "position": midgame()}),
+    )
+    .await;
+    assert_eq!(reply["type"], "ack");
+    send(&mut busy, json!({"type": "findMove"})).await;
+    assert_eq!(next_reply(&mut busy).await["type"], "info");
+
+    // a move that's still waiting for its turn when the client goes
+    let (mut ws, connection) = server.connect().await;
+    let reply = ws_request(
+        &mut ws,
+        json!({"type": "init", "startPos": "corner", "difficulty": "test", "position": midgame()}),
+    )
+    .await;
+    let id = reply["game"].clone();
+    send(&mut ws, json!({"type": "findMove"})).await;
+    let reply = ws_request(&mut ws, json!({"type": "getState"})).await;
+    assert_eq!(reply["type"], "state");
+    drop(ws);
+    tokio::time::timeout(Duration::from_secs(30), connection)
+        .await
+        .expect("Closing waited for the queued search")
+        .unwrap();
+
+    // the game can be resumed while the other search still runs
+    let (mut ws, _) = server.connect().await;
+    let reply = ws_request(&mut ws, json!({"type": "resume", "game": id})).await;
+    assert_eq!(
+        (&reply["type"], &reply["position"]),
+        (&json!("state"), &json!(midgame()))
+    );
+
+    let reply = ws_request(&mut busy, json!({"type": "stop"})).await;
+    assert_eq!(reply["type"], "move");
+    let reply = ws_request(&mut ws, json!({"type": "findMove"})).await;
+    assert_eq!(reply["type"], "move");
+}
@@ -0,0 +1,45 @@
+use blok_rs::board::{BoardState, StartPosition};
+
+#[test]
+pub fn renders_tiles_corners_and_start_squares() {
+    let mut game = BoardState::new(StartPosition::Corner);
+    game.do_move(0);
+
+    let rendered = game.to_string();
+    let lines: Vec<&str> = rendered.lines().collect();
+
+    assert_eq!(lines[0], "    a b c d e f g h i j k l m n");
+    assert_eq!(lines[1], "  1 A . . . . . . . . . . . . .");
+    assert_eq!(lines[3], "  3 A . a . . . . . . . . . . .");
+    assert_eq!(lines[4], "  4 A A . . . . . . . . . . . .");
+    assert_eq!(lines[5], "  5 . . a . . . . . . . . . . .");
+    assert_eq!(lines[14], " 14 . . . . . . . . . . . . . *");
+    assert_eq!(
+        lines[15],
+        "B to move, score A 5 - B 0, 0 null move(s), corner"
+    );
+    assert!(lines[16].starts_with("A remaining: Y5 N5"));
+    assert!(lines[17].starts_with("B remaining: L5 Y5"));
+}
+
+#[test]
+pub fn ansi_rendering_has_same_layout() {
+    let game = BoardState::new(StartPosition::Middle);
+
+    let plain = game.to_string();
+    let coloured = game.display_ansi().to_string();
+
+    assert!(coloured.contains("\x1b["));
+    let stripped: String = coloured
+        .split("\x1b[")
+        .enumerate()
+        .map(|(i, part)| {
+            if i == 0 {
+                part
+            } else {
+                &part[part.find('m').unwrap() + 1..]
+            }
+        })
+        .collect();
+    assert_eq!(stripped, plain);
+}
@@ -0,0 +1,90 @@
+use blok_rs::board::{BoardState, GameResult, Player, Score, StartPosition};
+use blok_rs::movegen;
+use blok_rs::record::{GameRecord, ReplayError};
+
+mod common;
+
+use common::play_min_moves;
+
+#[test]
+pub fn enums_use_camel_case() {
+    assert_eq!(
+        serde_json::to_string(&StartPosition::MiddleBlokee).unwrap(),
+        "\"middleBlokee\""
+    );
+    assert_eq!(serde_json::to_string(&Player::Black).unwrap(), "\"black\"");
+    assert_eq!(
+        serde_json::to_string(&GameResult::PlayerAWon).unwrap(),
+        "\"playerAWon\""
+    );
+    assert_eq!(
+        serde_json::to_string(&Score {
+            player_a: 3,
+            player_b: 4
+        })
+        .unwrap(),
+        r#"{"playerA":3,"playerB":4}"#
+    );
+}
+
+#[test]
+pub fn board_round_trip() {
+    let mut board = BoardState::new(StartPosition::Middle);
+    play_min_moves(&mut board, 10);
+
+    let json = serde_json::to_string(&board).unwrap();
+    assert!(!json.contains("CornerMoves"));
+
+    let parsed: BoardState = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.to_notation(), board.to_notation());
+
+    let mut expected = movegen::generate_moves(&board);
+    let mut moves = movegen::generate_moves(&parsed);
+    expected.sort();
+    moves.sort();
+    assert_eq!(moves, expected);
+}
+
+#[test]
+pub fn invalid_board_is_rejected() {
+    let mut value = serde_json::to_value(BoardState::new(StartPosition::Corner)).unwrap();
+    value["playerARemaining"] = serde_json::json!(0x1ffffe);
+
+    assert!(serde_json::from_value::<BoardState>(value).is_err());
+}
+
+#[test]
+pub fn game_record_round_trip() {
+    let mut board = BoardState::new(StartPosition::Corner);
+    let mut record = GameRecord::new(StartPosition::Corner);
+    record
+        .metadata
+        .insert("white".to_string(), "hce-latest".to_string());
+    record.moves = play_min_moves(&mut board, 200);
+    record.set_outcome(&board);
+
+    let json = serde_json::to_string(&record).unwrap();
+    let parsed: GameRecord = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, record);
+
+    let replayed = parsed.replay().unwrap();
+    assert_eq!(replayed.to_notation(), board.to_notation());
+    assert_eq!(Some(replayed.game_result()), record.result);
+}
+
+#[test]
+pub fn replay_rejects_illegal_moves() {
+    let mut record = GameRecord::new(StartPosition::Corner);
+    let mut board = BoardState::new(StartPosition::Corner);
+    record.moves = play_min_moves(&mut board, 2);
+    // the first move played again
+    record.moves.push(record.moves[0]);
+
+    assert_eq!(
+        record.replay().unwrap_err(),
+        ReplayError::IllegalMove {
+            ply: 2,
+            mov: record.moves[0]
+        }
+    );
+}
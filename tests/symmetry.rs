@@ -0,0 +1,165 @@
+use blok_rs::board::{BoardState, Player, PositionError, StartPosition};
+use blok_rs::mcts::MonteCarlo;
+use blok_rs::movegen;
+use blok_rs::symmetry::{
+    Symmetry, SymmetryError, canonical_form, equivalent_moves, symmetries, transform_board,
+    transform_move, unique_moves,
+};
+
+const START_POSITIONS: [StartPosition; 3] = [
+    StartPosition::Corner,
+    StartPosition::Middle,
+    StartPosition::MiddleBlokee,
+];
+
+fn sorted(mut moves: Vec<u32>) -> Vec<u32> {
+    moves.sort();
+    moves
+}
+
+#[test]
+pub fn symmetries_of_start_positions() {
+    assert_eq!(
+        symmetries(StartPosition::Corner),
+        vec![
+            (Symmetry::Identity, false),
+            (Symmetry::Transpose, false),
+            (Symmetry::AntiTranspose, true),
+            (Symmetry::Rotate180, true),
+        ]
+    );
+    assert_eq!(
+        symmetries(StartPosition::MiddleBlokee),
+        vec![
+            (Symmetry::Identity, false),
+            (Symmetry::Transpose, true),
+            (Symmetry::AntiTranspose, false),
+            (Symmetry::Rotate180, true),
+        ]
+    );
+}
+
+// Playing the transformed moves gives the transformed position, with the transformed moves
+// legal at every step.
+#[test]
+pub fn transformed_games_are_legal() {
+    for start_position in START_POSITIONS {
+        for (symmetry, swap) in symmetries(start_position) {
+            let mut board = BoardState::new(start_position);
+            let mut mirrored_moves = Vec::new();
+
+            for ply in 0..16 {
+                if board.is_game_over() {
+                    break;
+                }
+                let moves = movegen::generate_moves(&board);
+                let m = moves[(ply * 7) % moves.len()];
+                board.do_move(m);
+                mirrored_moves.push(transform_move(m, symmetry, swap));
+            }
+
+            let transformed = transform_board(&board, symmetry).unwrap();
+
+            // a colour swap changes who moves first, so replay from the transformed start
+            let mut mirrored = transform_board(&BoardState::new(start_position), symmetry).unwrap();
+            for &m in &mirrored_moves {
+                mirrored.try_do_move(m).unwrap();
+            }
+
+            assert_eq!(mirrored.to_notation(), transformed.to_notation());
+            assert_eq!(
+                sorted(movegen::generate_moves(&mirrored)),
+                sorted(movegen::generate_moves(&transformed))
+            );
+
+            let expected: Vec<u32> = movegen::generate_moves(&board)
+                .into_iter()
+                .map(|m| transform_move(m, symmetry, swap))
+                .collect();
+            assert_eq!(
+                sorted(movegen::generate_moves(&transformed)),
+                sorted(expected)
+            );
+        }
+    }
+}
+
+#[test]
+pub fn canonical_form_is_shared_by_images() {
+    for start_position in START_POSITIONS {
+        let mut board = BoardState::new(start_position);
+        for _ in 0..6 {
+            let m = *movegen::generate_moves(&board).iter().max().unwrap();
+            board.do_move(m);
+        }
+
+        let (canonical, _) = canonical_form(&board).unwrap();
+        for (symmetry, _) in symmetries(start_position) {
+            let image = transform_board(&board, symmetry).unwrap();
+            assert_eq!(
+                canonical_form(&image).unwrap().0.to_notation(),
+                canonical.to_notation()
+            );
+
+            let back = transform_board(&image, symmetry.inverse()).unwrap();
+            assert_eq!(back.to_notation(), board.to_notation());
+        }
+
+        let (_, symmetry) = canonical_form(&board).unwrap();
+        assert_eq!(
+            transform_board(&board, symmetry).unwrap().to_notation(),
+            canonical.to_notation()
+        );
+    }
+}
+
+#[test]
+pub fn invalid_positions_are_not_transformed() {
+    let mut board = BoardState::new(StartPosition::Corner);
+    board.do_move(*movegen::generate_moves(&board).iter().max().unwrap());
+    // White's piece is back in hand but still on the board
+    board.player_a_remaining = 0x1fffff;
+
+    assert_eq!(
+        transform_board(&board, Symmetry::Transpose).unwrap_err(),
+        SymmetryError::InvalidPosition(PositionError::TileCountMismatch(Player::White))
+    );
+}
+
+#[test]
+pub fn symmetric_root_moves_are_pruned() {
+    let board = BoardState::new(StartPosition::Corner);
+    let moves = movegen::generate_moves(&board);
+    let unique = unique_moves(&board, &moves);
+
+    assert!(unique.len() < moves.len());
+
+    // every legal move is equivalent to exactly one representative
+    for &m in &moves {
+        let class = equivalent_moves(&board, m);
+        assert!(class.iter().all(|c| moves.contains(c)));
+        assert_eq!(class.iter().filter(|c| unique.contains(c)).count(), 1);
+    }
+
+    // after an asymmetric first move nothing is pruned
+    let mut board = board;
+    let first = *moves
+        .iter()
+        .find(|&&m| equivalent_moves(&BoardState::new(StartPosition::Corner), m).len() > 1)
+        .unwrap();
+    board.do_move(first);
+    let replies = movegen::generate_moves(&board);
+    assert_eq!(unique_moves(&board, &replies), replies);
+}
+
+#[test]
+pub fn search_with_root_pruning() {
+    let board = BoardState::new(StartPosition::Middle);
+    let mut mcts = MonteCarlo::new();
+    mcts.prune_root_symmetry = true;
+    mcts.run_search(&board, "test");
+
+    let expected = unique_moves(&board, &movegen::generate_moves(&board));
+    assert_eq!(mcts.nodes[0].children.len(), expected.len());
+    assert!(expected.contains(&mcts.best_play().unwrap()));
+}
@@ -1,6 +1,12 @@
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
 use blok_rs::board::{BoardState, StartPosition};
 use blok_rs::mcts::MonteCarloNode;
+use blok_rs::mcts::SearchBudget;
 use blok_rs::mcts::monte_carlo::MonteCarlo;
+use blok_rs::movegen::generate_moves;
 
 #[test]
 pub fn is_valid_tree() {
@@ -14,20 +20,107 @@ pub fn is_valid_tree() {
     }
 }
 
+#[test]
+pub fn stopped_search_still_has_a_best_play() {
+    let game = BoardState::new(StartPosition::Corner);
+    let mut mcts = MonteCarlo::new();
+    mcts.stop = Some(Arc::new(AtomicBool::new(true)));
+    mcts.run_search(&game, "hard");
+
+    assert_eq!(mcts.get_stats().1, 1);
+    assert!(!mcts.is_root_fully_expanded());
+    assert!(mcts.best_play().is_ok());
+    for node in &mcts.nodes {
+        assert!(is_valid_node(&mcts.nodes, node));
+    }
+}
+
+#[test]
+pub fn reroot_keeps_the_subtree() {
+    // a few moves in, so searches are quicker than from the empty board
+    let mut game = BoardState::new(StartPosition::Corner);
+    for _ in 0..12 {
+        let m = *generate_moves(&game).iter().min().unwrap();
+        game.do_move(m);
+    }
+    let mut mcts = MonteCarlo::new();
+    mcts.run_search(&game, "test");
+
+    for node in &mcts.nodes {
+        assert!(is_valid_node(&mcts.nodes, node));
+    }
+    let play = mcts.best_play().unwrap();
+    let child_plays = mcts.nodes[mcts.nodes[0].child_node(play)].n_plays;
+    assert!(mcts.reroot(play));
+    // the new root drops the visit it had as a leaf
+    assert_eq!(mcts.get_stats().1, child_plays - 1);
+    assert_eq!(mcts.nodes[0].parent_idx, None);
+    for node in &mcts.nodes {
+        assert!(is_valid_node(&mcts.nodes, node));
+    }
+
+    // the search carries on from the kept tree
+    game.do_move(play);
+    mcts.run_search(&game, "test");
+    assert_eq!(mcts.get_stats().1, child_plays - 1 + 1_000);
+    for node in &mcts.nodes {
+        assert!(is_valid_node(&mcts.nodes, node));
+    }
+
+    // a search from any other position starts a new tree
+    let mut other = game.clone();
+    other.do_move(*generate_moves(&other).iter().min().unwrap());
+    mcts.run_search(&other, "test");
+    assert_eq!(mcts.get_stats().1, 1_000);
+    for node in &mcts.nodes {
+        assert!(is_valid_node(&mcts.nodes, node));
+    }
+
+    // a move that was never tried clears the tree
+    assert!(!mcts.reroot(0xffff));
+    assert!(mcts.nodes.is_empty());
+}
+
+#[test]
+pub fn progress_is_reported_during_the_search() {
+    let mut game = BoardState::new(StartPosition::Corner);
+    for _ in 0..12 {
+        let m = *generate_moves(&game).iter().min().unwrap();
+        game.do_move(m);
+    }
+    let mut mcts = MonteCarlo::new();
+    mcts.progress_interval = Duration::ZERO;
+
+    let mut reports = Vec::new();
+    mcts.run_budget_with_progress(&game, SearchBudget::Iterations(1_000), &mut |progress| {
+        reports.push(progress.clone())
+    });
+
+    assert!(!reports.is_empty());
+    assert!(
+        reports
+            .windows(2)
+            .all(|pair| pair[0].iterations < pair[1].iterations)
+    );
+    let last = reports.last().unwrap();
+    assert_eq!(last.iterations, 1_000);
+    assert_eq!(last.best.play, mcts.best_play().unwrap());
+    assert_eq!(last.pv, mcts.principal_variation(last.best.play));
+    assert_eq!(last.pv[0], last.best.play);
+}
+
 // check that the number of visits to the node is equal to the sum of the visits to the children
-pub fn is_valid_node(all_nodes: &Vec<MonteCarloNode>, node: &MonteCarloNode) -> bool {
+pub fn is_valid_node(all_nodes: &[MonteCarloNode], node: &MonteCarloNode) -> bool {
     let visits = node.n_plays;
     let mut children_visits = 0;
-    for child_idx in node.children.values() {
-        if let Some(child_idx) = child_idx {
-            children_visits += all_nodes[*child_idx].n_plays;
-        }
+    for child_idx in node.children.values().flatten() {
+        children_visits += all_nodes[*child_idx].n_plays;
     }
 
     // All nodes except the root node should have one more visit than the sum of the visits to the children (because of the visits to any child was it iself)
     if node.own_idx == 0 {
-        return visits == children_visits;
+        visits == children_visits
     } else {
-        return visits == children_visits + 1;
+        visits == children_visits + 1
     }
 }